@@ -0,0 +1,187 @@
+use egui_wgpu::wgpu;
+
+// Same syntax as `--adapter`
+pub const ADAPTER_ENV: &str = "WINIT_EGUI_ADAPTER";
+
+// Which adapter the app runs on. Sources in increasing priority: the settings
+// file, `WINIT_EGUI_ADAPTER`, then `--adapter` / `--fallback-adapter`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AdapterChoice {
+    // whatever `request_adapter` prefers for the surface
+    Default,
+    // the software adapter (llvmpipe, WARP, ...)
+    Fallback,
+    // index into `enumerate`, or text contained in "name (backend)", ignoring case
+    Named(String),
+}
+
+impl AdapterChoice {
+    pub fn parse(value: &str) -> Self {
+        match value.trim() {
+            "" | "default" => AdapterChoice::Default,
+            "fallback" => AdapterChoice::Fallback,
+            name => AdapterChoice::Named(name.to_string()),
+        }
+    }
+
+    // Inverse of `parse`
+    pub fn to_setting(&self) -> String {
+        match self {
+            AdapterChoice::Default => "default".to_string(),
+            AdapterChoice::Fallback => "fallback".to_string(),
+            AdapterChoice::Named(name) => name.clone(),
+        }
+    }
+
+    fn matches(&self, index: usize, info: &wgpu::AdapterInfo) -> bool {
+        let AdapterChoice::Named(query) = self else {
+            return false;
+        };
+        match query.parse::<usize>() {
+            Ok(wanted) => wanted == index,
+            Err(_) => label(info).to_lowercase().contains(&query.to_lowercase()),
+        }
+    }
+}
+
+pub fn label(info: &wgpu::AdapterInfo) -> String {
+    format!("{} ({:?})", info.name, info.backend)
+}
+
+// Every adapter on every backend compiled in
+pub fn enumerate(instance: &wgpu::Instance) -> Vec<wgpu::Adapter> {
+    instance.enumerate_adapters(wgpu::Backends::all())
+}
+
+// Falls back to the default adapter when the choice matches nothing usable
+pub async fn select(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface<'_>,
+    choice: &AdapterChoice,
+) -> wgpu::Adapter {
+    let chosen = match choice {
+        AdapterChoice::Default => None,
+        AdapterChoice::Fallback => {
+            instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    force_fallback_adapter: true,
+                    compatible_surface: Some(surface),
+                })
+                .await
+        }
+        AdapterChoice::Named(_) => enumerate(instance)
+            .into_iter()
+            .enumerate()
+            .find(|(index, adapter)| choice.matches(*index, &adapter.get_info()) && adapter.is_surface_supported(surface))
+            .map(|(_, adapter)| adapter),
+    };
+    if let Some(adapter) = chosen {
+        return adapter;
+    }
+    if *choice != AdapterChoice::Default {
+        println!("No adapter for {:?} can draw to the window; using the default", choice.to_setting());
+    }
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+            compatible_surface: Some(surface),
+        })
+        .await
+        .expect("Failed to find an appropriate adapter")
+}
+
+// What the adapter list in the UI shows, gathered once per device
+pub struct AdapterSummary {
+    pub info: wgpu::AdapterInfo,
+    pub limits: wgpu::Limits,
+    pub features: wgpu::Features,
+    pub supports_surface: bool,
+}
+
+impl AdapterSummary {
+    pub fn all(instance: &wgpu::Instance, surface: &wgpu::Surface<'_>) -> Vec<Self> {
+        enumerate(instance)
+            .iter()
+            .map(|adapter| AdapterSummary {
+                info: adapter.get_info(),
+                limits: adapter.limits(),
+                features: adapter.features(),
+                supports_surface: adapter.is_surface_supported(surface),
+            })
+            .collect()
+    }
+}
+
+// `wgpu::Limits` has no field iterator, so take the names and values from its Debug output
+fn limit_rows(limits: &wgpu::Limits) -> Vec<(String, String)> {
+    format!("{limits:#?}")
+        .lines()
+        .filter_map(|line| line.trim().trim_end_matches(',').split_once(": "))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+fn info_ui(ui: &mut egui::Ui, summary: &AdapterSummary) {
+    let info = &summary.info;
+    egui::Grid::new("info").num_columns(2).show(ui, |ui| {
+        let rows = [
+            ("Backend", format!("{:?}", info.backend)),
+            ("Type", format!("{:?}", info.device_type)),
+            ("Vendor / device", format!("{:#06x} / {:#06x}", info.vendor, info.device)),
+            ("Driver", format!("{} {}", info.driver, info.driver_info).trim().to_string()),
+            ("Features", format!("{} supported", summary.features.iter().count())),
+            ("Draws to window", summary.supports_surface.to_string()),
+        ];
+        for (name, value) in rows {
+            ui.label(name);
+            ui.label(value);
+            ui.end_row();
+        }
+    });
+    ui.collapsing("Limits", |ui| {
+        egui::Grid::new("limits").num_columns(2).striped(true).show(ui, |ui| {
+            for (name, value) in limit_rows(&summary.limits) {
+                ui.label(name);
+                ui.monospace(value);
+                ui.end_row();
+            }
+        });
+    });
+}
+
+// Returns true when the user picked a different adapter
+pub fn ui(
+    ui: &mut egui::Ui,
+    adapters: &[AdapterSummary],
+    current: &wgpu::AdapterInfo,
+    choice: &mut AdapterChoice,
+) -> bool {
+    ui.label(format!("Running on {}", label(current)));
+    let mut switched = false;
+    ui.horizontal(|ui| {
+        for (option, text) in [(AdapterChoice::Default, "Default"), (AdapterChoice::Fallback, "Software fallback")] {
+            if ui.selectable_label(*choice == option, text).clicked() && *choice != option {
+                *choice = option;
+                switched = true;
+            }
+        }
+    });
+    for (index, summary) in adapters.iter().enumerate() {
+        ui.push_id(index, |ui| {
+            let option = AdapterChoice::Named(label(&summary.info));
+            let running = summary.info == *current;
+            egui::CollapsingHeader::new(format!("{index}: {}{}", label(&summary.info), if running { " ●" } else { "" }))
+                .show(ui, |ui| {
+                    info_ui(ui, summary);
+                    let button = ui.add_enabled(summary.supports_surface && !running, egui::Button::new("Use this adapter"));
+                    if button.clicked() {
+                        *choice = option;
+                        switched = true;
+                    }
+                });
+        });
+    }
+    switched
+}
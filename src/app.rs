@@ -1,38 +1,74 @@
+use crate::adapter::{self, AdapterChoice, AdapterSummary};
 use crate::app_renderer::AppRenderer;
+use crate::buddhabrot::BuddhabrotRenderer;
+use crate::camera::{Camera, CameraMode};
+use crate::cube_layer::CubeLayer;
 use crate::egui_tools::EguiRenderer;
-use egui_wgpu::wgpu::{BindGroup, RenderPassDescriptor, SurfaceError};
+use crate::fractal_renderer::FractalRenderer;
+use crate::headless::read_texture_rgba;
+use crate::layer::{FrameContext, LayerInput, LayerStack};
+use crate::pipeline_cache::PipelineCache;
+use crate::post::PostChain;
+use crate::profiler::Profiler;
+use crate::raymarch::RaymarchRenderer;
+use crate::recorder::Recorder;
+use crate::render_scale::RenderScale;
+use crate::screen_layer::ScreenShaderLayer;
+use crate::screenshot::Screenshots;
+use crate::settings::Settings;
+use crate::shape_renderer::ShapesLayer;
+use crate::tonemap::{TonemapPass, HDR_FORMAT};
+use crate::viewport::{SceneViewport, DISPLAY_FORMAT};
+use egui_wgpu::wgpu::SurfaceError;
 use egui_wgpu::{wgpu, ScreenDescriptor};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use winit::application::ApplicationHandler;
 use winit::dpi::PhysicalSize;
-use winit::event::WindowEvent;
-use winit::event_loop::ActiveEventLoop;
+use winit::event::{ElementState, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow};
+use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::{Window, WindowId};
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct ShaderUniforms {
-    // WGSL vec3<f32> needs 16-byte alignment
-    // time: f32,          // 4 bytes
-    resolution: [f32; 2], // 8 bytes (total 12)
-    mouse_pos: [f32; 2],  // 8 bytes (total 20)
-    base_color: [f32; 4], // <- Changed to vec4 in shader (16 bytes)
-    _padding: [f32; 4],
-    // Total size: 4 + 8 + 8 + 16 = 36 bytes (288 bits)
-}
-
 pub struct AppState {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub surface_config: wgpu::SurfaceConfiguration,
+    // formats the surface can be configured with
+    surface_formats: Vec<wgpu::TextureFormat>,
+    // whether egui draws through an sRGB view of the surface; applied at the start of the next frame
+    pub srgb_output: bool,
+    srgb_view_formats: bool,
+    // the Auto modes plus whatever the surface reports
+    present_modes: Vec<wgpu::PresentMode>,
+    // present mode and latency are applied at the start of the next frame and saved
+    pub settings: Settings,
+    pub adapter_info: wgpu::AdapterInfo,
+    adapters: Vec<AdapterSummary>,
+    // set by the adapter list; the app then re-creates the state on the new device
+    pub switch_adapter: bool,
+    pub pipeline_cache: PipelineCache,
+    // set from wgpu's device lost callback, which may run on another thread
+    device_lost: Arc<AtomicBool>,
+    // set on an uncaptured wgpu error, after which the app exits
+    device_error: Arc<AtomicBool>,
     pub surface: wgpu::Surface<'static>,
     pub scale_factor: f32,
     pub egui_renderer: EguiRenderer,
-    pub render_pipeline: wgpu::RenderPipeline,
-    pub uniforms: ShaderUniforms,
-    pub bind_group: BindGroup,
-    uniform_buffer: wgpu::Buffer,
-   // pub app_renderer: AppRenderer,
+    pub layers: LayerStack,
+    pub viewport: SceneViewport,
+    pub screenshots: Screenshots,
+    pub recorder: Recorder,
+    pub camera: Camera,
+    pub tonemap: TonemapPass,
+    pub post: PostChain,
+    pub render_scale: RenderScale,
+    pub profiler: Profiler,
+    // MSAA for the scene and egui; applied at the start of the next frame
+    pub msaa_samples: u32,
+    pub supported_sample_counts: Vec<u32>,
 }
 
 impl AppState {
@@ -42,24 +78,28 @@ impl AppState {
         window: &Window,
         width: u32,
         height: u32,
+        adapter_override: Option<&AdapterChoice>,
     ) -> Self {
-        let power_pref = wgpu::PowerPreference::default();
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: power_pref,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .expect("Failed to find an appropriate adapter");
-
-        let features = wgpu::Features::empty();
+        let mut settings = Settings::load();
+        let adapter = adapter::select(instance, &surface, adapter_override.unwrap_or(&settings.adapter)).await;
+        let adapter_info = adapter.get_info();
+        println!("Using {}", adapter::label(&adapter_info));
+
+        // Lets MSAA use every sample count the adapter supports, not just 1 and 4,
+        // the profiler time GPU passes and pipelines load from the disk cache,
+        // wherever the adapter allows
+        let features = adapter.features()
+            & (wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+                | wgpu::Features::TIMESTAMP_QUERY
+                | wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS
+                | wgpu::Features::PIPELINE_CACHE);
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
                     required_features: features,
-                    required_limits: Default::default(),
+                    // software adapters often miss the WebGPU defaults
+                    required_limits: adapter.limits(),
                     memory_hints: Default::default(),
                 },
                 None,
@@ -67,142 +107,219 @@ impl AppState {
             .await
             .expect("Failed to create device");
 
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let lost = device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // `Destroyed` is the app dropping the device itself
+            if reason == wgpu::DeviceLostReason::Unknown {
+                println!("Device lost: {message}");
+                lost.store(true, Ordering::Relaxed);
+            }
+        });
+        let lost = device_lost.clone();
+        let device_error = Arc::new(AtomicBool::new(false));
+        let failed = device_error.clone();
+        device.on_uncaptured_error(Box::new(move |error| {
+            // every call fails once the device is gone; it is replaced on the next frame
+            if !lost.load(Ordering::Relaxed) {
+                eprintln!("wgpu error: {error}");
+                failed.store(true, Ordering::Relaxed);
+            }
+        }));
+
+        let pipeline_cache = PipelineCache::load(&device, &adapter_info);
+        let cache = pipeline_cache.get();
+
         let swapchain_capabilities = surface.get_capabilities(&adapter);
-        let selected_format = wgpu::TextureFormat::Bgra8UnormSrgb;
-        let swapchain_format = swapchain_capabilities
-            .formats
-            .iter()
-            .find(|d| **d == selected_format)
-            .expect("failed to select proper surface texture format!");
+        let swapchain_format = choose_surface_format(&swapchain_capabilities.formats);
+        let srgb_view_formats = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS);
+
+        // Screenshots with the UI copy straight from the surface where allowed
+        let mut usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        if swapchain_capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+            usage |= wgpu::TextureUsages::COPY_SRC;
+        }
+
+        let mut present_modes = vec![wgpu::PresentMode::AutoVsync, wgpu::PresentMode::AutoNoVsync];
+        present_modes.extend(swapchain_capabilities.present_modes.iter().copied());
+        // the saved mode may come from another adapter or monitor
+        if !present_modes.contains(&settings.present_mode) {
+            settings.present_mode = wgpu::PresentMode::AutoVsync;
+        }
 
         let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: *swapchain_format,
+            usage,
+            format: swapchain_format,
             width,
             height,
-            present_mode: wgpu::PresentMode::AutoVsync,
-            desired_maximum_frame_latency: 0,
+            present_mode: settings.present_mode,
+            desired_maximum_frame_latency: settings.frame_latency,
             alpha_mode: swapchain_capabilities.alpha_modes[0],
             view_formats: vec![],
         };
 
         surface.configure(&device, &surface_config);
 
-        let egui_renderer = EguiRenderer::new(&device, surface_config.format, None, 1, window);
-
-        let scale_factor = 1.0;
-
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("screen_shader.wgsl").into()),
-        });
+        // egui draws in the surface format, the scene in HDR
+        let hdr_sample_counts = supported_sample_counts(&adapter, &device, HDR_FORMAT);
+        let supported_sample_counts: Vec<u32> = supported_sample_counts(&adapter, &device, surface_config.format)
+            .into_iter()
+            .filter(|count| hdr_sample_counts.contains(count))
+            .collect();
+        let msaa_samples = if supported_sample_counts.contains(&4) { 4 } else { 1 };
 
-        // Uniform buffer
-        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Uniform Buffer"),
-            size: std::mem::size_of::<ShaderUniforms>() as u64,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let mut egui_renderer = EguiRenderer::new(&device, surface_config.format, None, msaa_samples, window);
+        let mut viewport = SceneViewport::new(&device, egui_renderer.renderer_mut(), PhysicalSize::new(width, height));
 
-        // Bind group layout
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-            label: Some("uniform_bind_group_layout"),
-        });
-
-        // Bind group
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: uniform_buffer.as_entire_binding(),
-            }],
-            label: Some("uniform_bind_group"),
-        });
+        let scale_factor = 1.0;
 
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"), // 1.
-                buffers: &[], // 2. 
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState { // 3.
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState { // 4.
-                    format: surface_config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
+        // Drawn bottom to top; only the background and shader are on at startup
+        let mut layers = LayerStack::default();
+        layers.push(
+            AppRenderer::new(wgpu::Color {
+                r: 0.1,
+                g: 0.2,
+                b: 0.3,
+                a: 1.0,
             }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList, // 1.
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw, // 2.
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: None, // 1.
-            multisample: wgpu::MultisampleState {
-                count: 1, // 2.
-                mask: !0, // 3.
-                alpha_to_coverage_enabled: false, // 4.
-            },
-            multiview: None, // 5.
-            cache: None, // 6.
-        });
-
-        // Setup AppRendere and set background "clear color"
-        // let app_renderer = AppRenderer::new(wgpu::Color {
-        //     r: 0.1,
-        //     g: 0.2,
-        //     b: 0.3,
-        //     a: 1.0,
-        // });
+            true,
+        );
+        layers.push(ScreenShaderLayer::new(&device, HDR_FORMAT, cache), true);
+        layers.push(FractalRenderer::new(&device, HDR_FORMAT, cache), false);
+        layers.push(BuddhabrotRenderer::new(&device, HDR_FORMAT, width, height, cache), false);
+        layers.push(RaymarchRenderer::new(&device, HDR_FORMAT, cache), false);
+        layers.push(CubeLayer::new(&device, HDR_FORMAT, cache), false);
+        layers.push(ShapesLayer::new(&device, HDR_FORMAT, cache), false);
+        viewport.set_sample_count(&device, msaa_samples);
+        layers.set_sample_count(&device, msaa_samples);
+        let adapters = AdapterSummary::all(instance, &surface);
+        let tonemap = TonemapPass::new(&device, DISPLAY_FORMAT, cache);
+        let post = PostChain::new(&device, DISPLAY_FORMAT, cache);
+        let profiler = Profiler::new(&device, &queue);
 
         Self {
             device,
             queue,
             surface,
+            srgb_output: surface_config.format.is_srgb(),
+            surface_formats: swapchain_capabilities.formats,
+            srgb_view_formats,
+            present_modes,
+            settings,
+            adapters,
+            adapter_info,
+            switch_adapter: false,
+            pipeline_cache,
+            device_lost,
+            device_error,
             surface_config,
             egui_renderer,
             scale_factor,
-            render_pipeline,
-            uniforms: ShaderUniforms {
-                // time: 0.0,
-                resolution: [800.0, 600.0], // Initial window size
-                mouse_pos: [0.5, 0.5],     // Normalized coords
-                base_color: [0.1, 0.2, 0.3, 1.0],
-                _padding: [0.0, 0.0, 0.0, 0.0],
-            },
-            bind_group,
-            uniform_buffer
-            // app_renderer
+            layers,
+            viewport,
+            screenshots: Screenshots::new(),
+            recorder: Recorder::new(),
+            camera: Camera::orbit(glam::Vec3::ZERO, 3.0),
+            tonemap,
+            post,
+            render_scale: RenderScale::new(),
+            profiler,
+            msaa_samples,
+            supported_sample_counts,
+        }
+    }
+
+    // Rebuilds multisampled targets and pipelines after the sample count changed
+    fn apply_msaa(&mut self) {
+        if self.egui_renderer.msaa_samples() != self.msaa_samples {
+            self.egui_renderer.set_msaa_samples(&self.device, &self.queue, self.msaa_samples);
+            self.viewport.register(&self.device, self.egui_renderer.renderer_mut());
+        }
+        if self.viewport.sample_count() != self.msaa_samples {
+            self.viewport.set_sample_count(&self.device, self.msaa_samples);
+            self.layers.set_sample_count(&self.device, self.msaa_samples);
+        }
+    }
+
+    fn msaa_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("MSAA");
+            for &count in &self.supported_sample_counts {
+                let label = if count == 1 { "Off".to_string() } else { format!("{count}x") };
+                ui.selectable_value(&mut self.msaa_samples, count, label);
+            }
+        });
+    }
+
+    // Either encoding works for the output: egui's shader encodes sRGB itself when
+    // drawing into a linear view. Both look the same; this is for checking that.
+    fn apply_output_format(&mut self) {
+        let format = if self.srgb_output {
+            self.surface_config.format.add_srgb_suffix()
+        } else {
+            self.surface_config.format.remove_srgb_suffix()
+        };
+        if format == self.egui_renderer.output_color_format() {
+            return;
+        }
+        // Reconfigure with the other variant when the surface lists it, otherwise
+        // keep the format and view it differently
+        if self.surface_formats.contains(&format) {
+            self.surface_config.format = format;
+            self.surface_config.view_formats.clear();
+        } else {
+            self.surface_config.view_formats = vec![format];
         }
+        self.surface.configure(&self.device, &self.surface_config);
+        self.egui_renderer.set_output_color_format(&self.device, &self.queue, format);
+        self.viewport.register(&self.device, self.egui_renderer.renderer_mut());
+    }
+
+    fn apply_present_mode(&mut self) {
+        if self.surface_config.present_mode == self.settings.present_mode
+            && self.surface_config.desired_maximum_frame_latency == self.settings.frame_latency
+        {
+            return;
+        }
+        self.surface_config.present_mode = self.settings.present_mode;
+        self.surface_config.desired_maximum_frame_latency = self.settings.frame_latency;
+        self.surface.configure(&self.device, &self.surface_config);
+        if let Err(err) = self.settings.save() {
+            println!("Failed to save settings: {err}");
+        }
+    }
+
+    fn present_ui(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("Present mode")
+            .selected_text(present_mode_label(self.settings.present_mode))
+            .show_ui(ui, |ui| {
+                for &mode in &self.present_modes {
+                    ui.selectable_value(&mut self.settings.present_mode, mode, present_mode_label(mode));
+                }
+            });
+        ui.add(egui::Slider::new(&mut self.settings.frame_latency, 1..=3).text("Frame latency"));
+    }
+
+    // Whether the surface can be viewed in both sRGB and linear encoding
+    fn can_switch_encoding(&self) -> bool {
+        let format = self.surface_config.format;
+        let other = if format.is_srgb() { format.remove_srgb_suffix() } else { format.add_srgb_suffix() };
+        other != format && (self.surface_formats.contains(&other) || self.srgb_view_formats)
+    }
+
+    fn output_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Output");
+            ui.add_enabled_ui(self.can_switch_encoding(), |ui| {
+                ui.selectable_value(&mut self.srgb_output, true, "sRGB");
+                ui.selectable_value(&mut self.srgb_output, false, "Linear");
+            });
+        })
+        .response
+        .on_hover_text(format!("Surface {:?}, drawn as {:?}", self.surface_config.format, self.egui_renderer.output_color_format()));
     }
 
     fn resize_surface(&mut self, width: u32, height: u32) {
@@ -210,28 +327,84 @@ impl AppState {
         self.surface_config.height = height;
         self.surface.configure(&self.device, &self.surface_config);
     }
+}
 
-    pub fn update_uniforms(&mut self, new_uniforms: ShaderUniforms) {
-        self.uniforms = new_uniforms;
-        self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.uniforms]));
+fn present_mode_label(mode: wgpu::PresentMode) -> &'static str {
+    match mode {
+        wgpu::PresentMode::AutoVsync => "Auto (vsync)",
+        wgpu::PresentMode::AutoNoVsync => "Auto (no vsync)",
+        wgpu::PresentMode::Fifo => "Fifo",
+        wgpu::PresentMode::FifoRelaxed => "Fifo relaxed",
+        wgpu::PresentMode::Immediate => "Immediate",
+        wgpu::PresentMode::Mailbox => "Mailbox",
     }
 }
 
+// 8-bit RGBA or BGRA where available since those come in both sRGB and linear
+// variants, otherwise whatever the surface prefers
+fn choose_surface_format(formats: &[wgpu::TextureFormat]) -> wgpu::TextureFormat {
+    let preferred = formats.iter().find(|format| {
+        matches!(
+            format.remove_srgb_suffix(),
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Rgba8Unorm
+        )
+    });
+    // sRGB first among the variants of the preferred format
+    let srgb = preferred.map(|format| format.add_srgb_suffix()).filter(|format| formats.contains(format));
+    srgb.or(preferred.copied())
+        .or(formats.first().copied())
+        .expect("The surface supports no texture formats")
+}
+
+// MSAA sample counts offered in the UI that `format` supports as a render target
+fn supported_sample_counts(adapter: &wgpu::Adapter, device: &wgpu::Device, format: wgpu::TextureFormat) -> Vec<u32> {
+    let features = if device
+        .features()
+        .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+    {
+        adapter.get_texture_format_features(format)
+    } else {
+        format.guaranteed_format_features(device.features())
+    };
+    [1, 2, 4, 8]
+        .into_iter()
+        .filter(|&count| features.flags.sample_count_supported(count))
+        .collect()
+}
+
 pub struct App {
     instance: wgpu::Instance,
     state: Option<AppState>,
     window: Option<Arc<Window>>,
-    cursor_position: Option<(f32, f32)>,
+    keys_down: HashSet<KeyCode>,
+    // scene time in seconds; follows the wall clock except while recording
+    time: f32,
+    last_frame: Instant,
+    // time spent in window events since the last frame, for the profiler
+    event_time: Duration,
+    // whether the last frame showed something moving, so the next one follows right away
+    animating: bool,
+    // when to draw again without new input; None waits for input
+    next_redraw: Option<Instant>,
+    // from the command line or environment; wins over the settings file until
+    // an adapter is picked in the UI
+    adapter_override: Option<AdapterChoice>,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(adapter_override: Option<AdapterChoice>) -> Self {
         let instance = egui_wgpu::wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
         Self {
             instance,
+            adapter_override,
             state: None,
             window: None,
-            cursor_position: None
+            keys_down: HashSet::new(),
+            time: 0.0,
+            last_frame: Instant::now(),
+            event_time: Duration::ZERO,
+            animating: false,
+            next_redraw: Some(Instant::now()),
         }
     }
 
@@ -253,6 +426,7 @@ impl App {
             &window,
             initial_width,
             initial_width,
+            self.adapter_override.as_ref(),
         )
         .await;
 
@@ -260,24 +434,90 @@ impl App {
         self.state.get_or_insert(state);
     }
 
+    // Re-creates the device and everything on it, for a lost device or a newly
+    // chosen adapter. The window, surface and the user's settings carry over.
+    fn rebuild_state(&mut self) {
+        let (Some(window), Some(mut old)) = (self.window.clone(), self.state.take()) else {
+            return;
+        };
+        if let Err(err) = old.settings.save() {
+            println!("Failed to save settings: {err}");
+        }
+        // a lost device has nothing left to hand out
+        if !old.device_lost.load(Ordering::Relaxed) {
+            if let Err(err) = old.pipeline_cache.save() {
+                println!("Failed to save pipeline cache: {err}");
+            }
+        }
+        let size = window.inner_size();
+        let mut state = pollster::block_on(AppState::new(
+            &self.instance,
+            old.surface,
+            &window,
+            size.width.max(1),
+            size.height.max(1),
+            self.adapter_override.as_ref(),
+        ));
+
+        let (device, queue) = (&state.device, &state.queue);
+        state.egui_renderer.adopt_state(device, queue, old.egui_renderer);
+        state.layers.restore(old.layers);
+        state.camera = old.camera;
+        state.tonemap.exposure = old.tonemap.exposure;
+        state.tonemap.curve = old.tonemap.curve;
+        state.post.effects = old.post.effects;
+        state.render_scale = old.render_scale;
+        state.profiler.open = old.profiler.open;
+        state.screenshots = old.screenshots;
+        state.recorder = old.recorder;
+        state.scale_factor = old.scale_factor;
+        // the new adapter may not support everything the old one did
+        if state.supported_sample_counts.contains(&old.msaa_samples) {
+            state.msaa_samples = old.msaa_samples;
+        }
+        if state.can_switch_encoding() {
+            state.srgb_output = old.srgb_output;
+        }
+        self.state = Some(state);
+    }
+
     fn handle_resized(&mut self, width: u32, height: u32) {
         if width > 0 && height > 0 {
             self.state.as_mut().unwrap().resize_surface(width, height);
         }
     }
 
-    fn handle_redraw(&mut self) {
+    // Errors are fatal and end the app
+    fn handle_redraw(&mut self) -> Result<(), String> {
+        self.next_redraw = None;
         // Attempt to handle minimizing window
         if let Some(window) = self.window.as_ref() {
             if let Some(min) = window.is_minimized() {
                 if min {
                     println!("Window is minimized");
-                    return;
+                    return Ok(());
                 }
             }
         }
 
+        if let Some(state) = &self.state {
+            if state.switch_adapter {
+                // the UI choice wins over the command line from now on
+                self.adapter_override = None;
+                self.rebuild_state();
+            } else if state.device_lost.load(Ordering::Relaxed) {
+                self.rebuild_state();
+            }
+        }
+
         let state = self.state.as_mut().unwrap();
+        state.profiler.begin_frame(&state.device);
+        state.profiler.cpu("Events", std::mem::take(&mut self.event_time));
+
+        // Nothing moves between on-demand frames, so scene time only runs while animating
+        let now = Instant::now();
+        let real_dt = if self.animating { (now - self.last_frame).as_secs_f32() } else { 0.0 };
+        self.last_frame = now;
 
         let screen_descriptor = ScreenDescriptor {
             size_in_pixels: [state.surface_config.width, state.surface_config.height],
@@ -285,26 +525,36 @@ impl App {
                 * state.scale_factor,
         };
 
-        let surface_texture = state.surface.get_current_texture();
-
-        match surface_texture {
-            Err(SurfaceError::Outdated) => {
-                // Ignoring outdated to allow resizing and minimization
-                println!("wgpu surface outdated");
-                return;
+        state.apply_output_format();
+        state.apply_present_mode();
+        let surface_texture = match state.surface.get_current_texture() {
+            Ok(surface_texture) => surface_texture,
+            Err(err @ (SurfaceError::Outdated | SurfaceError::Lost)) => {
+                // e.g. after a resize or a display change; the next frame uses the new swap chain
+                println!("wgpu surface {err:?}, reconfiguring");
+                state.surface.configure(&state.device, &state.surface_config);
+                self.next_redraw = Some(Instant::now());
+                return Ok(());
+            }
+            Err(SurfaceError::Timeout) => {
+                println!("wgpu surface timed out, skipping the frame");
+                self.next_redraw = Some(Instant::now());
+                return Ok(());
             }
-            Err(_) => {
-                surface_texture.expect("Failed to acquire next swap chain texture");
-                return;
+            Err(SurfaceError::OutOfMemory) => {
+                return Err("out of memory while acquiring the next frame".to_string());
+            }
+            Err(err) => {
+                println!("wgpu surface error: {err}, skipping the frame");
+                self.next_redraw = Some(Instant::now());
+                return Ok(());
             }
-            Ok(_) => {}
         };
 
-        let surface_texture = surface_texture.unwrap();
-
-        let surface_view = surface_texture
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let surface_view = surface_texture.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(state.egui_renderer.output_color_format()),
+            ..Default::default()
+        });
 
         let mut encoder = state
             .device
@@ -312,73 +562,131 @@ impl App {
 
         let window = self.window.as_ref().unwrap();
 
-        
-        // state.app_renderer.render(&mut encoder, &surface_view);
+        state.apply_msaa();
 
-        let new_uniforms = ShaderUniforms {
-            // time: 0.0, // Implement time tracking
-            resolution: [state.surface_config.width as f32, state.surface_config.height as f32],
-            mouse_pos: [self.cursor_position.unwrap().0, self.cursor_position.unwrap().1], // Implement mouse tracking
-            base_color: [0.1, 0.2, 0.3, 1.0], // Teal: R=0, G=0.5, B=0.5,
-            _padding: [0.0, 0.0, 0.0, 0.0],
-        };
-        state.update_uniforms(new_uniforms);
-        // state.update_uniforms(&state.queue, new_uniforms);
+        // Requested during an earlier frame, so it sees that frame's UI changes
+        let capture = state.screenshots.take_request();
 
+        let ui_start = Instant::now();
+        state.egui_renderer.begin_frame(window);
+        let ctx = state.egui_renderer.context().clone();
+        state.screenshots.handle_hotkeys(&ctx);
 
-        {
-            // 1.
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[
-                    // This is what @location(0) in the fragment shader targets
-                    Some(wgpu::RenderPassColorAttachment {
-                        view: &surface_view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(
-                                wgpu::Color {
-                                    r: 0.1,
-                                    g: 0.2,
-                                    b: 0.3,
-                                    a: 1.0,
-                                }
-                            ),
-                            store: wgpu::StoreOp::Store,
+        egui::TopBottomPanel::top("menu_bar").show(&ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("Capture", |ui| state.screenshots.menu_ui(ui));
+                ui.menu_button("View", |ui| ui.checkbox(&mut state.profiler.open, "Profiler"));
+            });
+        });
+
+        let mut mode = state.camera.mode;
+        egui::SidePanel::left("controls")
+            .resizable(true)
+            .show(&ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.heading("Layers");
+                    state.layers.ui(ui);
+
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label("Camera");
+                        ui.selectable_value(&mut mode, CameraMode::Orbit, "Orbit");
+                        ui.selectable_value(&mut mode, CameraMode::Fly, "Fly");
+                    });
+                    ui.label("Drag to look, scroll to zoom, WASD / QE to move");
+
+                    ui.separator();
+                    state.render_scale.ui(ui);
+                    state.msaa_ui(ui);
+                    state.output_ui(ui);
+                    state.present_ui(ui);
+                    state.tonemap.ui(ui);
+
+                    ui.separator();
+                    ui.collapsing("Post effects", |ui| state.post.ui(ui));
+                    ui.collapsing("Recording", |ui| state.recorder.ui(ui, self.time));
+                    ui.collapsing("Adapter", |ui| {
+                        if adapter::ui(ui, &state.adapters, &state.adapter_info, &mut state.settings.adapter) {
+                            state.switch_adapter = true;
                         }
-                    })
-                ],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
+                    });
+                });
             });
-        
-            // NEW!
-            render_pass.set_pipeline(&state.render_pipeline); // 2.
-            render_pass.set_bind_group(0, &state.bind_group, &[]);
-            render_pass.draw(0..3, 0..1);
-            // render_pass.draw(0..3, 0..1); // 3.
+        state.camera.set_mode(mode);
+        state.profiler.window(&ctx);
+
+        // The scene takes whatever space the panels leave, or a fixed size while recording
+        state.viewport.set_fixed_size(state.recorder.size());
+        // idle gaps between on-demand frames say nothing about the rendering cost
+        if self.animating {
+            state.render_scale.update(real_dt);
+        }
+        state.viewport.set_render_scale(state.render_scale.scale);
+        let filter = state.render_scale.upscale.filter_mode();
+        state.viewport.set_filter(&state.device, state.egui_renderer.renderer_mut(), filter);
+        let inputs = egui::CentralPanel::default()
+            .frame(egui::Frame::NONE)
+            .show(&ctx, |ui| state.viewport.ui(ui))
+            .inner;
+        if state.viewport.sync_size(&state.device, state.egui_renderer.renderer_mut(), ctx.pixels_per_point()) {
+            state.layers.resize(&state.device, state.viewport.size());
         }
 
-        {
-            state.egui_renderer.begin_frame(window);
-
-            egui::Window::new("Shader Control")
-                .show(state.egui_renderer.context(), |ui| {
-                    let mut color = [
-                        state.uniforms.base_color[0],
-                        state.uniforms.base_color[1],
-                        state.uniforms.base_color[2],
-                    ];
-                    
-                    ui.color_edit_button_rgb(&mut color);
-                    // if color != [state.uniforms.base_color[0], state.uniforms.base_color[1], state.uniforms.base_color[2]] {
-                    //     let mut new_uniforms = state.uniforms;
-                    //     new_uniforms.base_color = [color[0], color[1], color[2], 1.0];
-                    //     state.update_uniforms(new_uniforms);
-                    // }
-                });
+        let size = state.viewport.size();
+        for input in &inputs {
+            if state.layers.input(input, size) {
+                continue;
+            }
+            match *input {
+                LayerInput::Drag { delta } => state.camera.rotate(delta.0, delta.1),
+                LayerInput::Scroll { delta, .. } => state.camera.zoom(delta),
+                LayerInput::Click { .. } => {}
+            }
+        }
+
+        state.profiler.cpu("egui build", ui_start.elapsed());
+        let scene_start = Instant::now();
+
+        let (time, dt) = state.recorder.frame_time().unwrap_or((self.time + real_dt, real_dt));
+        self.time = time;
+        state.camera.update(&self.keys_down, dt);
+        let frame = FrameContext {
+            device: &state.device,
+            queue: &state.queue,
+            size,
+            camera: &state.camera,
+            time,
+            cursor: state.viewport.cursor(),
+        };
+        state.viewport.set_depth(&state.device, state.layers.uses_depth());
+        state.layers.render(&frame, &mut encoder, &state.viewport.target(), Some(&mut state.profiler));
+        // With post effects enabled the chain sits between tonemapping and the viewport
+        if state.post.is_active() {
+            state.post.resize(&state.device, size);
+            state.profiler.begin_gpu(&mut encoder, "Tonemap");
+            state.tonemap.render(&state.device, &state.queue, &mut encoder, state.viewport.hdr_view(), state.post.input());
+            state.profiler.end_gpu(&mut encoder);
+            state.profiler.begin_gpu(&mut encoder, "Post effects");
+            state.post.render(&state.device, &state.queue, &mut encoder, state.viewport.view(), time);
+            state.profiler.end_gpu(&mut encoder);
+        } else {
+            state.profiler.begin_gpu(&mut encoder, "Tonemap");
+            state.tonemap.render(
+                &state.device,
+                &state.queue,
+                &mut encoder,
+                state.viewport.hdr_view(),
+                state.viewport.view(),
+            );
+            state.profiler.end_gpu(&mut encoder);
+        }
 
+        // Same layer as the central panel, painted after the scene image
+        let painter = egui::Painter::new(ctx.clone(), egui::LayerId::background(), state.viewport.rect());
+        state.layers.overlay(&painter, &frame);
+        state.profiler.cpu("Scene encoding", scene_start.elapsed());
+
+        let egui_output = {
             // egui::Window::new("winit + egui + wgpu says hello!")
             //     .resizable(true)
             //     .vscroll(true)
@@ -405,7 +713,8 @@ impl App {
             //         });
             //     });
 
-            state.egui_renderer.end_frame_and_draw(
+            state.profiler.begin_gpu(&mut encoder, "egui");
+            let output = state.egui_renderer.end_frame_and_draw(
                 &state.device,
                 &state.queue,
                 &mut encoder,
@@ -413,10 +722,66 @@ impl App {
                 &surface_view,
                 screen_descriptor,
             );
-        }
+            state.profiler.end_gpu(&mut encoder);
+            state.profiler.cpu("Tessellation", output.tessellation);
+            output
+        };
 
+        state.profiler.resolve(&mut encoder);
+        let submit_start = Instant::now();
         state.queue.submit(Some(encoder.finish()));
+        state.profiler.cpu("Submit", submit_start.elapsed());
+
+        if let Some(capture) = capture {
+            // the readback only understands 8-bit RGBA and BGRA
+            let readable = matches!(
+                state.surface_config.format.remove_srgb_suffix(),
+                wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Bgra8Unorm
+            );
+            let with_ui =
+                capture.include_ui && readable && state.surface_config.usage.contains(wgpu::TextureUsages::COPY_SRC);
+            let note = (capture.include_ui && !with_ui).then_some("scene only, this surface can't be read back");
+            let texture = if with_ui { &surface_texture.texture } else { state.viewport.texture() };
+            let pixels = read_texture_rgba(&state.device, &state.queue, texture);
+            let mut metadata = vec![("Software".to_string(), "winit-egui".to_string())];
+            metadata.extend(state.layers.metadata());
+            metadata.push(("Render scale".to_string(), state.render_scale.metadata()));
+            metadata.push(("Tonemapping".to_string(), state.tonemap.metadata()));
+            if let Some(post) = state.post.metadata() {
+                metadata.push(("Post effects".to_string(), post));
+            }
+            metadata.push(("Camera".to_string(), format!("{:?}", state.camera)));
+            let size = PhysicalSize::new(texture.width(), texture.height());
+            match pixels {
+                Ok(pixels) => state.screenshots.finish(pixels, size, &metadata, note),
+                Err(err) => state.screenshots.fail(&err),
+            }
+        }
+
+        if state.recorder.is_recording() {
+            match read_texture_rgba(&state.device, &state.queue, state.viewport.texture()) {
+                Ok(pixels) => state.recorder.record_frame(&pixels, state.viewport.size()),
+                Err(err) => state.recorder.fail(&err),
+            }
+        }
+
+        let present_start = Instant::now();
         surface_texture.present();
+        state.profiler.cpu("Present", present_start.elapsed());
+        state.profiler.end_frame();
+
+        // Keep drawing while anything animates, otherwise wait for input or for egui
+        self.animating = state.layers.animating()
+            || state.post.animating()
+            || state.recorder.is_recording()
+            || !self.keys_down.is_empty();
+        // a capture or clipboard copy is finished by the next frame, so it mustn't wait for input
+        self.next_redraw = if self.animating || state.screenshots.pending() {
+            Some(now)
+        } else {
+            now.checked_add(egui_output.repaint_delay)
+        };
+        Ok(())
     }
 }
 
@@ -429,8 +794,11 @@ impl ApplicationHandler for App {
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        let event_start = Instant::now();
+        let is_redraw = matches!(event, WindowEvent::RedrawRequested);
         // let egui render to process the event first
-        self.state
+        let response = self
+            .state
             .as_mut()
             .unwrap()
             .egui_renderer
@@ -442,17 +810,62 @@ impl ApplicationHandler for App {
                 event_loop.exit();
             }
             WindowEvent::RedrawRequested => {
-                self.handle_redraw();
-
-                self.window.as_ref().unwrap().request_redraw();
+                if let Err(err) = self.handle_redraw() {
+                    eprintln!("error: {err}");
+                    event_loop.exit();
+                    return;
+                }
             }
             WindowEvent::Resized(new_size) => {
                 self.handle_resized(new_size.width, new_size.height);
+                self.window.as_ref().unwrap().request_redraw();
             }
-            WindowEvent::CursorMoved { position, .. } => {
-                self.cursor_position = Some((position.x as f32, position.y as f32));
+            WindowEvent::KeyboardInput { event, .. } => {
+                if let PhysicalKey::Code(code) = event.physical_key {
+                    if event.state == ElementState::Pressed && !response.consumed {
+                        self.keys_down.insert(code);
+                    } else if event.state == ElementState::Released {
+                        self.keys_down.remove(&code);
+                    }
+                }
             }
             _ => (),
         }
+        // redraws are timed by the profiler itself
+        if !is_redraw {
+            if response.repaint {
+                self.window.as_ref().unwrap().request_redraw();
+            }
+            self.event_time += event_start.elapsed();
+        }
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(state) = &mut self.state {
+            if let Err(err) = state.pipeline_cache.save() {
+                println!("Failed to save pipeline cache: {err}");
+            }
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(window) = &self.window else {
+            return;
+        };
+        // a validation error is a bug that rebuilding the device would only repeat
+        if self.state.as_ref().is_some_and(|state| state.device_error.load(Ordering::Relaxed)) {
+            eprintln!("error: stopping after a wgpu error");
+            event_loop.exit();
+            return;
+        }
+        match self.next_redraw {
+            Some(at) if at <= Instant::now() => {
+                self.next_redraw = None;
+                window.request_redraw();
+                event_loop.set_control_flow(ControlFlow::Wait);
+            }
+            Some(at) => event_loop.set_control_flow(ControlFlow::WaitUntil(at)),
+            None => event_loop.set_control_flow(ControlFlow::Wait),
+        }
     }
 }
\ No newline at end of file
@@ -1,34 +1,53 @@
+use crate::layer::{FrameContext, Layer, RenderTarget};
 use egui_wgpu::wgpu;
+use std::any::Any;
 
+// Bottom of the layer stack: clears the target to a solid color
 pub struct AppRenderer {
     clear_color: wgpu::Color,
-    // render_pipeline: wgpu::RenderPipeline,
 }
 
 impl AppRenderer {
     pub fn new(clear_color: wgpu::Color) -> Self {
         Self { clear_color }
     }
+}
 
-    pub fn render(
-        &self,
-        encoder: &mut wgpu::CommandEncoder,
-        surface_view: &wgpu::TextureView,) {
-            let _rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Background Clear"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: surface_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(self.clear_color),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
+impl Layer for AppRenderer {
+    fn name(&self) -> &str {
+        "Background"
+    }
 
-            
+    fn restore(&mut self, old: Box<dyn Any>) {
+        let Ok(old) = old.downcast::<Self>() else {
+            return;
+        };
+        self.clear_color = old.clear_color;
     }
-}
\ No newline at end of file
+
+    fn render(&mut self, _frame: &FrameContext, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget) {
+        let _rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Background Clear"),
+            color_attachments: &[Some(target.color_attachment(wgpu::LoadOp::Clear(self.clear_color)))],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+    }
+
+    fn metadata(&self) -> Option<String> {
+        Some(format!("{:?}", self.clear_color))
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let mut color = [self.clear_color.r as f32, self.clear_color.g as f32, self.clear_color.b as f32];
+        if ui.color_edit_button_rgb(&mut color).changed() {
+            self.clear_color = wgpu::Color {
+                r: color[0] as f64,
+                g: color[1] as f64,
+                b: color[2] as f64,
+                a: 1.0,
+            };
+        }
+    }
+}
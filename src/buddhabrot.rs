@@ -0,0 +1,564 @@
+use crate::complex::Complex;
+use crate::layer::{FrameContext, Layer, RenderTarget};
+use crate::mandelbrot::{self, FractalPlot};
+use crate::uniform::{self, impl_uniform, UniformBuffer};
+use egui_wgpu::wgpu;
+use std::any::Any;
+use std::sync::Mutex;
+use winit::dpi::PhysicalSize;
+
+const WORKGROUP_SIZE: u32 = 64;
+const WORKGROUPS_PER_FRAME: u32 = 256;
+const SAMPLES_PER_INVOCATION: u32 = 4;
+pub const SAMPLES_PER_FRAME: u64 = (WORKGROUPS_PER_FRAME * WORKGROUP_SIZE * SAMPLES_PER_INVOCATION) as u64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BuddhabrotParams {
+    pub center: Complex,
+    pub width: f64,
+    // Max iterations of the orbits that land in the r, g and b channels (Nebulabrot).
+    // Equal values give a plain grayscale Buddhabrot.
+    pub bands: [u32; 3],
+}
+
+impl Default for BuddhabrotParams {
+    fn default() -> Self {
+        Self {
+            center: Complex::new(-0.5, 0.0),
+            width: 3.2,
+            bands: [5000, 500, 50],
+        }
+    }
+}
+
+impl BuddhabrotParams {
+    fn max_iter(&self) -> u32 {
+        self.bands.iter().copied().max().unwrap_or(0)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BuddhabrotUniforms {
+    center: [f32; 2],
+    inc: f32,
+    seed: u32,
+    size: [u32; 2],
+    samples: u32,
+    scale: u32,
+    bands: [u32; 4],
+}
+
+impl_uniform!(BuddhabrotUniforms { center, inc, seed, size, samples, scale, bands });
+
+// Progressive GPU Buddhabrot: every frame adds a batch of random samples to an
+// atomic density buffer, then draws the normalized density to the target.
+pub struct BuddhabrotRenderer {
+    params: BuddhabrotParams,
+    // density resolution, the target size divided by `scale`
+    size: PhysicalSize<u32>,
+    scale: u32,
+    frame: u32,
+    total_samples: u64,
+    needs_clear: bool,
+    uniform_buffer: UniformBuffer<BuddhabrotUniforms>,
+    density_buffer: wgpu::Buffer,
+    maxima_buffer: wgpu::Buffer,
+    compute_layout: wgpu::BindGroupLayout,
+    display_layout: wgpu::BindGroupLayout,
+    compute_bind_group: wgpu::BindGroup,
+    display_bind_group: wgpu::BindGroup,
+    sample_pipeline: wgpu::ComputePipeline,
+    maxima_pipeline: wgpu::ComputePipeline,
+    format: wgpu::TextureFormat,
+    cache: Option<wgpu::PipelineCache>,
+    display_shader: wgpu::ShaderModule,
+    display_pipeline_layout: wgpu::PipelineLayout,
+    display_pipeline: wgpu::RenderPipeline,
+}
+
+impl BuddhabrotRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let (size, scale) = Self::density_size(device, PhysicalSize::new(width, height));
+
+        // both shaders read the same uniforms
+        let compute_source = include_str!("buddhabrot.wgsl");
+        let display_source = include_str!("buddhabrot_display.wgsl");
+        uniform::check_layout::<BuddhabrotUniforms>("buddhabrot.wgsl", compute_source, 0, 0);
+        uniform::check_layout::<BuddhabrotUniforms>("buddhabrot_display.wgsl", display_source, 0, 0);
+        let uniform_buffer = UniformBuffer::new(device, "Buddhabrot Uniform Buffer");
+        let maxima_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Buddhabrot Maxima Buffer"),
+            size: 3 * 4,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let density_buffer = Self::create_density_buffer(device, size);
+
+        let layout_entry = |binding, visibility, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty,
+            count: None,
+        };
+        let uniform = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        let storage = |read_only| wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+
+        let compute_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("buddhabrot_compute_bind_group_layout"),
+            entries: &[
+                layout_entry(0, wgpu::ShaderStages::COMPUTE, uniform),
+                layout_entry(1, wgpu::ShaderStages::COMPUTE, storage(false)),
+                layout_entry(2, wgpu::ShaderStages::COMPUTE, storage(false)),
+            ],
+        });
+        let display_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("buddhabrot_display_bind_group_layout"),
+            entries: &[
+                layout_entry(0, wgpu::ShaderStages::FRAGMENT, uniform),
+                layout_entry(1, wgpu::ShaderStages::FRAGMENT, storage(true)),
+                layout_entry(2, wgpu::ShaderStages::FRAGMENT, storage(true)),
+            ],
+        });
+
+        let (compute_bind_group, display_bind_group) = Self::create_bind_groups(
+            device,
+            &compute_layout,
+            &display_layout,
+            &uniform_buffer,
+            &density_buffer,
+            &maxima_buffer,
+        );
+
+        let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Buddhabrot Compute Shader"),
+            source: wgpu::ShaderSource::Wgsl(compute_source.into()),
+        });
+        let display_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Buddhabrot Display Shader"),
+            source: wgpu::ShaderSource::Wgsl(display_source.into()),
+        });
+
+        let compute_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Buddhabrot Compute Pipeline Layout"),
+            bind_group_layouts: &[&compute_layout],
+            push_constant_ranges: &[],
+        });
+        let compute_pipeline = |label, entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&compute_pipeline_layout),
+                module: &compute_shader,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache,
+            })
+        };
+        let sample_pipeline = compute_pipeline("Buddhabrot Sample Pipeline", "cs_sample");
+        let maxima_pipeline = compute_pipeline("Buddhabrot Maxima Pipeline", "cs_maxima");
+
+        let display_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Buddhabrot Display Pipeline Layout"),
+            bind_group_layouts: &[&display_layout],
+            push_constant_ranges: &[],
+        });
+        let display_pipeline = Self::create_display_pipeline(device, &display_pipeline_layout, &display_shader, target_format, 1, cache);
+
+        Self {
+            params: BuddhabrotParams::default(),
+            size,
+            scale,
+            frame: 0,
+            total_samples: 0,
+            needs_clear: true,
+            uniform_buffer,
+            density_buffer,
+            maxima_buffer,
+            compute_layout,
+            display_layout,
+            compute_bind_group,
+            display_bind_group,
+            sample_pipeline,
+            maxima_pipeline,
+            format: target_format,
+            cache: cache.cloned(),
+            display_shader,
+            display_pipeline_layout,
+            display_pipeline,
+        }
+    }
+
+    fn create_display_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Buddhabrot Display Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache,
+        })
+    }
+
+    // Bytes of the r, g, b hit counts for `size`, or an error when the device can't
+    // bind that much or dispatch the maxima pass over it
+    pub fn density_buffer_size(device: &wgpu::Device, size: PhysicalSize<u32>) -> Result<u64, String> {
+        let limits = device.limits();
+        let bytes = size.width as u64 * size.height as u64 * 3 * 4;
+        let max = u64::from(limits.max_storage_buffer_binding_size).min(limits.max_buffer_size);
+        if bytes > max {
+            return Err(format!(
+                "a {}x{} Buddhabrot needs a {bytes} byte density buffer, the device allows at most {max} bytes",
+                size.width, size.height
+            ));
+        }
+        let (x, y) = Self::maxima_workgroups(size);
+        let max = limits.max_compute_workgroups_per_dimension;
+        if x.max(y) > max {
+            return Err(format!(
+                "a {}x{} Buddhabrot needs {x}x{y} workgroups, the device allows at most {max} per dimension",
+                size.width, size.height
+            ));
+        }
+        Ok(bytes)
+    }
+
+    // The density resolution for a `target` sized view and the factor it was divided by.
+    // Views too large for the device get a coarser density instead of failing.
+    fn density_size(device: &wgpu::Device, target: PhysicalSize<u32>) -> (PhysicalSize<u32>, u32) {
+        let target = PhysicalSize::new(target.width.max(1), target.height.max(1));
+        // 1x1 always fits, so this ends at the latest when `scale` covers the larger side
+        (1..=target.width.max(target.height))
+            .map(|scale| (PhysicalSize::new(target.width.div_ceil(scale), target.height.div_ceil(scale)), scale))
+            .find(|(size, _)| Self::density_buffer_size(device, *size).is_ok())
+            .unwrap()
+    }
+
+    // one invocation per pixel, split over x and y to stay under the per-dimension limit
+    fn maxima_workgroups(size: PhysicalSize<u32>) -> (u32, u32) {
+        (size.width.div_ceil(WORKGROUP_SIZE), size.height)
+    }
+
+    fn create_density_buffer(device: &wgpu::Device, size: PhysicalSize<u32>) -> wgpu::Buffer {
+        // `size` comes from `density_size`, so it fits
+        let bytes = size.width as u64 * size.height as u64 * 3 * 4;
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Buddhabrot Density Buffer"),
+            size: bytes,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_groups(
+        device: &wgpu::Device,
+        compute_layout: &wgpu::BindGroupLayout,
+        display_layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &UniformBuffer<BuddhabrotUniforms>,
+        density_buffer: &wgpu::Buffer,
+        maxima_buffer: &wgpu::Buffer,
+    ) -> (wgpu::BindGroup, wgpu::BindGroup) {
+        let entries = [
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: density_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: maxima_buffer.as_entire_binding(),
+            },
+        ];
+        let compute = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("buddhabrot_compute_bind_group"),
+            layout: compute_layout,
+            entries: &entries,
+        });
+        let display = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("buddhabrot_display_bind_group"),
+            layout: display_layout,
+            entries: &entries,
+        });
+        (compute, display)
+    }
+
+    pub fn set_params(&mut self, params: BuddhabrotParams) {
+        if params != self.params {
+            self.params = params;
+            self.reset();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.needs_clear = true;
+        self.total_samples = 0;
+    }
+}
+
+impl Layer for BuddhabrotRenderer {
+    fn name(&self) -> &str {
+        "Buddhabrot"
+    }
+
+    fn restore(&mut self, old: Box<dyn Any>) {
+        let Ok(old) = old.downcast::<Self>() else {
+            return;
+        };
+        self.set_params(old.params);
+    }
+
+    // every frame adds samples
+    fn animating(&self) -> bool {
+        true
+    }
+
+    fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.display_pipeline = Self::create_display_pipeline(
+            device,
+            &self.display_pipeline_layout,
+            &self.display_shader,
+            self.format,
+            sample_count,
+            self.cache.as_ref(),
+        );
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, size: PhysicalSize<u32>) {
+        (self.size, self.scale) = Self::density_size(device, size);
+        self.density_buffer = Self::create_density_buffer(device, self.size);
+        (self.compute_bind_group, self.display_bind_group) = Self::create_bind_groups(
+            device,
+            &self.compute_layout,
+            &self.display_layout,
+            &self.uniform_buffer,
+            &self.density_buffer,
+            &self.maxima_buffer,
+        );
+        self.reset();
+    }
+
+    // Adds one batch of samples and draws the current density over `target`
+    fn render(&mut self, frame: &FrameContext, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget) {
+        if self.needs_clear {
+            encoder.clear_buffer(&self.density_buffer, 0, None);
+            self.needs_clear = false;
+        }
+        encoder.clear_buffer(&self.maxima_buffer, 0, None);
+
+        let inc = self.params.width / self.size.width as f64;
+        let max_iter = self.params.max_iter();
+        let uniforms = BuddhabrotUniforms {
+            center: [self.params.center.re as f32, self.params.center.im as f32],
+            inc: inc as f32,
+            seed: self.frame,
+            size: [self.size.width, self.size.height],
+            samples: SAMPLES_PER_INVOCATION,
+            scale: self.scale,
+            bands: [self.params.bands[0], self.params.bands[1], self.params.bands[2], max_iter],
+        };
+        self.uniform_buffer.write(frame.queue, uniforms);
+        self.frame = self.frame.wrapping_add(1);
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Buddhabrot Compute Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_bind_group(0, &self.compute_bind_group, &[]);
+            compute_pass.set_pipeline(&self.sample_pipeline);
+            compute_pass.dispatch_workgroups(WORKGROUPS_PER_FRAME, 1, 1);
+            compute_pass.set_pipeline(&self.maxima_pipeline);
+            let (x, y) = Self::maxima_workgroups(self.size);
+            compute_pass.dispatch_workgroups(x, y, 1);
+        }
+        self.total_samples += SAMPLES_PER_FRAME;
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Buddhabrot Display Pass"),
+            color_attachments: &[Some(target.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.display_pipeline);
+        render_pass.set_bind_group(0, &self.display_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn metadata(&self) -> Option<String> {
+        let mut metadata = format!("{:?}, {} samples", self.params, self.total_samples);
+        if self.scale > 1 {
+            metadata.push_str(&format!(", density at 1/{} resolution", self.scale));
+        }
+        Some(metadata)
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let mut params = self.params;
+        ui.horizontal(|ui| {
+            ui.label("Bands (r, g, b)");
+            for band in params.bands.iter_mut() {
+                ui.add(egui::DragValue::new(band).range(1..=100_000));
+            }
+        });
+        self.set_params(params);
+        ui.horizontal(|ui| {
+            ui.label(format!("{} samples", self.total_samples));
+            if ui.button("Reset").clicked() {
+                self.reset();
+            }
+        });
+        if self.scale > 1 {
+            ui.label(format!(
+                "Density at 1/{} resolution ({}x{}), the full size exceeds the device limits",
+                self.scale, self.size.width, self.size.height
+            ));
+        }
+    }
+}
+
+// splitmix64, good enough for sampling and reproducible from a seed
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// CPU reference implementation of the compute shader. Returns interleaved r, g, b
+// hit counts per pixel.
+pub fn render_density(params: &BuddhabrotParams, size: PhysicalSize<u32>, samples: u64, seed: u64) -> Vec<u32> {
+    let plot = FractalPlot::new(params.center, params.width, size);
+    let max_iter = params.max_iter();
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get()) as u64;
+    let len = size.width as usize * size.height as usize * 3;
+    let density = Mutex::new(vec![0u32; len]);
+
+    std::thread::scope(|scope| {
+        for t in 0..threads {
+            let plot = &plot;
+            let density = &density;
+            scope.spawn(move || {
+                let mut rng = Rng(seed ^ t.wrapping_mul(0x2545f4914f6cdd1d));
+                let mut local = vec![0u32; len];
+                let mut orbit = Vec::with_capacity(max_iter as usize);
+
+                for _ in 0..samples / threads + u64::from(t < samples % threads) {
+                    let c = Complex::new(rng.next_f64() * 4.0 - 2.0, rng.next_f64() * 4.0 - 2.0);
+                    if mandelbrot::in_main_cardioid(c) || mandelbrot::in_period2_bulb(c) {
+                        continue;
+                    }
+
+                    orbit.clear();
+                    let mut z = Complex::zero();
+                    while orbit.len() < max_iter as usize && z.norm_sqr() < 4.0 {
+                        z = z * z + c;
+                        orbit.push(z);
+                    }
+                    let n = orbit.len() as u32;
+                    if n == max_iter {
+                        continue;
+                    }
+
+                    for z in &orbit {
+                        let (x, y) = plot.get_pixel(*z);
+                        if x < 0.0 || y < 0.0 || x >= size.width as f64 || y >= size.height as f64 {
+                            continue;
+                        }
+                        let index = (y as usize * size.width as usize + x as usize) * 3;
+                        for (k, band) in params.bands.iter().enumerate() {
+                            if n < *band {
+                                local[index + k] += 1;
+                            }
+                        }
+                    }
+                }
+
+                let mut density = density.lock().unwrap();
+                for (total, count) in density.iter_mut().zip(local) {
+                    *total += count;
+                }
+            });
+        }
+    });
+
+    density.into_inner().unwrap()
+}
+
+// Same normalization as buddhabrot_display.wgsl, whose output is linear, encoded to
+// sRGB bytes the way an sRGB render target stores it
+pub fn density_to_rgba(density: &[u32]) -> Vec<u8> {
+    let mut peak = [1u32; 3];
+    for pixel in density.chunks_exact(3) {
+        for k in 0..3 {
+            peak[k] = peak[k].max(pixel[k]);
+        }
+    }
+    density
+        .chunks_exact(3)
+        .flat_map(|pixel| {
+            let channel = |k: usize| (srgb_encode((pixel[k] as f64 / peak[k] as f64).sqrt()) * 255.0).round() as u8;
+            [channel(0), channel(1), channel(2), 255]
+        })
+        .collect()
+}
+
+fn srgb_encode(linear: f64) -> f64 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
@@ -0,0 +1,96 @@
+struct Params {
+    center: vec2<f32>,
+    // complex-plane units per pixel
+    inc: f32,
+    seed: u32,
+    size: vec2<u32>,
+    // samples per invocation
+    samples: u32,
+    // target pixels per density pixel, see `fs_main` in buddhabrot_display.wgsl
+    scale: u32,
+    // max iterations for the r, g and b bands; w is the largest of the three
+    bands: vec4<u32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+
+@group(0) @binding(1)
+var<storage, read_write> density: array<atomic<u32>>;
+
+@group(0) @binding(2)
+var<storage, read_write> maxima: array<atomic<u32>, 3>;
+
+fn pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(state: ptr<function, u32>) -> f32 {
+    *state = pcg(*state);
+    return f32(*state) / 4294967295.0;
+}
+
+fn in_interior(c: vec2<f32>) -> bool {
+    // main cardioid and period-2 bulb never escape
+    let x = c.x - 0.25;
+    let q = x * x + c.y * c.y;
+    let bulb = (c.x + 1.0) * (c.x + 1.0) + c.y * c.y;
+    return q * (q + x) <= 0.25 * c.y * c.y || bulb <= 0.0625;
+}
+
+fn iterate(z: vec2<f32>, c: vec2<f32>) -> vec2<f32> {
+    return vec2(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+}
+
+@compute @workgroup_size(64)
+fn cs_sample(@builtin(global_invocation_id) id: vec3<u32>) {
+    var rng = pcg(id.x ^ pcg(params.seed));
+    let half_size = vec2<f32>(params.size) * 0.5;
+
+    for (var s = 0u; s < params.samples; s++) {
+        let c = vec2(random(&rng) * 4.0 - 2.0, random(&rng) * 4.0 - 2.0);
+        if in_interior(c) {
+            continue;
+        }
+
+        var z = vec2(0.0, 0.0);
+        var n = 0u;
+        while n < params.bands.w && dot(z, z) < 4.0 {
+            z = iterate(z, c);
+            n++;
+        }
+        if n == params.bands.w {
+            continue;
+        }
+
+        // Replay the escaping orbit and splat it into every band that accepts it
+        z = vec2(0.0, 0.0);
+        for (var i = 0u; i < n; i++) {
+            z = iterate(z, c);
+            let p = vec2(z.x - params.center.x, params.center.y - z.y) / params.inc + half_size;
+            if p.x < 0.0 || p.y < 0.0 || p.x >= f32(params.size.x) || p.y >= f32(params.size.y) {
+                continue;
+            }
+            let index = (u32(p.y) * params.size.x + u32(p.x)) * 3u;
+            for (var k = 0u; k < 3u; k++) {
+                if n < params.bands[k] {
+                    atomicAdd(&density[index + k], 1u);
+                }
+            }
+        }
+    }
+}
+
+@compute @workgroup_size(64)
+fn cs_maxima(@builtin(global_invocation_id) id: vec3<u32>) {
+    // dispatched as (width / 64, height) workgroups
+    if id.x >= params.size.x || id.y >= params.size.y {
+        return;
+    }
+    let pixel = id.y * params.size.x + id.x;
+    for (var k = 0u; k < 3u; k++) {
+        atomicMax(&maxima[k], atomicLoad(&density[pixel * 3u + k]));
+    }
+}
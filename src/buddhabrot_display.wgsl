@@ -0,0 +1,44 @@
+struct Params {
+    center: vec2<f32>,
+    inc: f32,
+    seed: u32,
+    size: vec2<u32>,
+    samples: u32,
+    scale: u32,
+    bands: vec4<u32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+
+@group(0) @binding(1)
+var<storage, read> density: array<u32>;
+
+@group(0) @binding(2)
+var<storage, read> maxima: array<u32, 3>;
+
+// Renders a full-screen triangle without vertex data
+@vertex
+fn vs_main(@builtin(vertex_index) vert_index: u32) -> @builtin(position) vec4<f32> {
+    let pos = array(
+        vec2(-1.0, -1.0),
+        vec2(3.0, -1.0),
+        vec2(-1.0, 3.0),
+    );
+    return vec4(pos[vert_index], 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32> {
+    // the density may be coarser than the target when the full size didn't fit
+    let pixel = vec2<u32>(frag_position.xy) / params.scale;
+    let index = (pixel.y * params.size.x + pixel.x) * 3u;
+
+    var color = vec3(0.0);
+    for (var k = 0u; k < 3u; k++) {
+        let peak = max(f32(maxima[k]), 1.0);
+        // square root keeps faint orbits visible next to the bright core
+        color[k] = sqrt(f32(density[index + k]) / peak);
+    }
+    return vec4(color, 1.0);
+}
@@ -0,0 +1,204 @@
+use crate::uniform::{impl_uniform, UniformBuffer};
+use egui_wgpu::wgpu;
+use glam::{Mat4, Vec3};
+use std::collections::HashSet;
+use winit::dpi::PhysicalSize;
+use winit::keyboard::KeyCode;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    // Rotates around `target`, scroll changes the distance
+    Orbit,
+    // Free movement with WASD / QE, mouse drag looks around
+    Fly,
+}
+
+#[derive(Debug)]
+pub struct Camera {
+    pub mode: CameraMode,
+    pub target: Vec3,
+    pub distance: f32,
+    pub position: Vec3,
+    // radians; yaw around +Y, pitch towards +Y
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fov_y: f32,
+    // clip planes of the projection
+    pub near: f32,
+    pub far: f32,
+    pub speed: f32,
+    pub sensitivity: f32,
+}
+
+impl Camera {
+    pub fn orbit(target: Vec3, distance: f32) -> Self {
+        let mut camera = Self {
+            mode: CameraMode::Orbit,
+            target,
+            distance,
+            position: Vec3::ZERO,
+            yaw: 0.6,
+            // looking slightly down at the target
+            pitch: -0.3,
+            fov_y: 60f32.to_radians(),
+            near: 0.01,
+            far: 100.0,
+            speed: 1.0,
+            sensitivity: 0.005,
+        };
+        camera.position = camera.eye();
+        camera
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        Vec3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            -self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    // forward, right, up
+    pub fn basis(&self) -> (Vec3, Vec3, Vec3) {
+        let forward = self.forward();
+        let right = forward.cross(Vec3::Y).normalize();
+        let up = right.cross(forward);
+        (forward, right, up)
+    }
+
+    pub fn eye(&self) -> Vec3 {
+        match self.mode {
+            CameraMode::Orbit => self.target - self.forward() * self.distance,
+            CameraMode::Fly => self.position,
+        }
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_to_rh(self.eye(), self.forward(), Vec3::Y)
+    }
+
+    // Right-handed with depth in 0..1, as wgpu expects
+    pub fn projection(&self, aspect: f32) -> Mat4 {
+        Mat4::perspective_rh(self.fov_y, aspect, self.near, self.far)
+    }
+
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == self.mode {
+            return;
+        }
+        // Keep the view where it is when switching
+        match mode {
+            CameraMode::Fly => self.position = self.eye(),
+            CameraMode::Orbit => self.target = self.position + self.forward() * self.distance,
+        }
+        self.mode = mode;
+    }
+
+    pub fn rotate(&mut self, dx: f32, dy: f32) {
+        let limit = std::f32::consts::FRAC_PI_2 - 0.01;
+        self.yaw += dx * self.sensitivity;
+        self.pitch = (self.pitch - dy * self.sensitivity).clamp(-limit, limit);
+    }
+
+    pub fn zoom(&mut self, scroll: f32) {
+        match self.mode {
+            CameraMode::Orbit => self.distance = (self.distance * (1.0 - scroll * 0.1)).max(0.01),
+            CameraMode::Fly => self.speed = (self.speed * (1.0 + scroll * 0.1)).clamp(0.01, 100.0),
+        }
+    }
+
+    pub fn update(&mut self, keys: &HashSet<KeyCode>, dt: f32) {
+        let (forward, right, _) = self.basis();
+        let mut motion = Vec3::ZERO;
+        for key in keys {
+            motion += match key {
+                KeyCode::KeyW => forward,
+                KeyCode::KeyS => -forward,
+                KeyCode::KeyD => right,
+                KeyCode::KeyA => -right,
+                KeyCode::KeyE => Vec3::Y,
+                KeyCode::KeyQ => -Vec3::Y,
+                _ => Vec3::ZERO,
+            };
+        }
+        if motion == Vec3::ZERO {
+            return;
+        }
+        let step = motion.normalize() * self.speed * dt;
+        match self.mode {
+            CameraMode::Orbit => self.target += step,
+            CameraMode::Fly => self.position += step,
+        }
+    }
+}
+
+// Layout of `struct Camera` in WGSL shaders that bind `CameraBuffer`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniforms {
+    pub view: Mat4,
+    pub projection: Mat4,
+    pub view_projection: Mat4,
+    pub eye: Vec3,
+    _padding: f32,
+}
+
+impl_uniform!(CameraUniforms { view, projection, view_projection, eye, _padding });
+
+impl CameraUniforms {
+    pub fn new(camera: &Camera, size: PhysicalSize<u32>) -> Self {
+        let view = camera.view();
+        let projection = camera.projection(size.width as f32 / size.height.max(1) as f32);
+        Self {
+            view,
+            projection,
+            view_projection: projection * view,
+            eye: camera.eye(),
+            _padding: 0.0,
+        }
+    }
+}
+
+// Camera uniforms on the GPU, visible to vertex and fragment stages. Layers that
+// draw 3D geometry bind it as group 0.
+pub struct CameraBuffer {
+    buffer: UniformBuffer<CameraUniforms>,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl CameraBuffer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let buffer = UniformBuffer::new(device, "Camera Uniform Buffer");
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("camera_bind_group_layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.binding(),
+            }],
+            label: Some("camera_bind_group"),
+        });
+        Self {
+            buffer,
+            layout,
+            bind_group,
+        }
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &Camera, size: PhysicalSize<u32>) {
+        self.buffer.write(queue, CameraUniforms::new(camera, size));
+    }
+}
@@ -0,0 +1,296 @@
+use crate::adapter::{self, AdapterChoice, ADAPTER_ENV};
+use crate::buddhabrot::{self, BuddhabrotParams, BuddhabrotRenderer};
+use crate::complex::Complex;
+use crate::fractal_renderer::FractalRenderer;
+use crate::headless::Headless;
+use crate::layer::LayerStack;
+use crate::mandelbrot::{self, FractalView, Palette};
+use egui_wgpu::wgpu;
+use std::time::Instant;
+use winit::dpi::PhysicalSize;
+
+pub const USAGE: &str = "usage: winit-egui [--adapter index|name] [--fallback-adapter] [--list-adapters]
+       winit-egui render-fractal [--mode mandelbrot|buddhabrot] \
+[--center x,y] [--width w] [--size WxH] [--iters n] [--palette file] [--subdivide] \
+[--samples n] [--bands r,g,b] [--gpu] [--fallback-adapter] [--out image.png]";
+
+// Options of the interactive app
+pub struct AppArgs {
+    // overrides `WINIT_EGUI_ADAPTER` and the settings file
+    pub adapter: Option<AdapterChoice>,
+    pub list_adapters: bool,
+}
+
+impl AppArgs {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut parsed = AppArgs {
+            adapter: None,
+            list_adapters: false,
+        };
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--adapter" => {
+                    let value = args.next().ok_or("missing value for --adapter")?;
+                    parsed.adapter = Some(AdapterChoice::parse(value));
+                }
+                "--fallback-adapter" => parsed.adapter = Some(AdapterChoice::Fallback),
+                "--list-adapters" => parsed.list_adapters = true,
+                other => return Err(format!("unknown argument {other}")),
+            }
+        }
+        Ok(parsed)
+    }
+
+    // The command line choice, else the environment's
+    pub fn adapter_override(&self) -> Option<AdapterChoice> {
+        self.adapter
+            .clone()
+            .or_else(|| std::env::var(ADAPTER_ENV).ok().map(|value| AdapterChoice::parse(&value)))
+    }
+}
+
+// Prints what `--adapter` can select
+pub fn list_adapters() {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    for (index, adapter) in adapter::enumerate(&instance).iter().enumerate() {
+        let info = adapter.get_info();
+        let driver = format!("{} {}", info.driver, info.driver_info);
+        println!("{index}: {} [{:?}, {}]", adapter::label(&info), info.device_type, driver.trim());
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FractalMode {
+    Mandelbrot,
+    Buddhabrot,
+}
+
+pub struct RenderArgs {
+    pub mode: FractalMode,
+    pub view: FractalView,
+    pub size: PhysicalSize<u32>,
+    pub palette: Option<String>,
+    pub subdivide: bool,
+    pub samples: u64,
+    pub bands: [u32; 3],
+    // render with the app's shaders on a headless device instead of the CPU
+    pub gpu: bool,
+    pub fallback_adapter: bool,
+    pub out: String,
+}
+
+impl RenderArgs {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut parsed = RenderArgs {
+            mode: FractalMode::Mandelbrot,
+            view: FractalView::default(),
+            size: PhysicalSize::new(1920, 1080),
+            palette: None,
+            subdivide: false,
+            samples: 10_000_000,
+            bands: BuddhabrotParams::default().bands,
+            gpu: false,
+            fallback_adapter: false,
+            out: "mandelbrot.png".to_string(),
+        };
+
+        let mut iters_given = false;
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {flag}"))
+            };
+            match flag.as_str() {
+                "--mode" => {
+                    parsed.mode = match value()?.as_str() {
+                        "mandelbrot" => FractalMode::Mandelbrot,
+                        "buddhabrot" => FractalMode::Buddhabrot,
+                        other => return Err(format!("unknown mode {other}")),
+                    };
+                }
+                "--center" => {
+                    parsed.view.center = value()?
+                        .parse::<Complex>()
+                        .map_err(|err| err.to_string())?;
+                }
+                "--width" => {
+                    parsed.view.width = value()?
+                        .parse()
+                        .ok()
+                        .filter(|w: &f64| *w > 0.0)
+                        .ok_or("--width must be a positive number")?;
+                }
+                "--size" => parsed.size = parse_size(value()?)?,
+                "--iters" => {
+                    parsed.view.max_iter = value()?
+                        .parse()
+                        .ok()
+                        .filter(|n: &u32| *n > 0)
+                        .ok_or("--iters must be a positive integer")?;
+                    iters_given = true;
+                }
+                "--palette" => parsed.palette = Some(value()?.clone()),
+                "--subdivide" => parsed.subdivide = true,
+                "--samples" => {
+                    parsed.samples = value()?
+                        .parse()
+                        .ok()
+                        .filter(|n: &u64| *n > 0)
+                        .ok_or("--samples must be a positive integer")?;
+                }
+                "--bands" => parsed.bands = parse_bands(value()?)?,
+                "--gpu" => parsed.gpu = true,
+                "--fallback-adapter" => parsed.fallback_adapter = true,
+                "--out" => parsed.out = value()?.clone(),
+                other => return Err(format!("unknown argument {other}")),
+            }
+        }
+
+        if parsed.fallback_adapter && !parsed.gpu {
+            return Err("--fallback-adapter needs --gpu".to_string());
+        }
+        if parsed.gpu && parsed.palette.is_some() {
+            return Err("--palette is only supported on the CPU".to_string());
+        }
+        // the GPU adds a fixed batch of samples per frame and counts frames in a u32
+        let max_gpu_samples = u64::from(u32::MAX) * buddhabrot::SAMPLES_PER_FRAME;
+        if parsed.gpu && parsed.samples > max_gpu_samples {
+            return Err(format!("--samples can be at most {max_gpu_samples} with --gpu"));
+        }
+        if parsed.gpu && parsed.subdivide {
+            return Err("--subdivide is only supported on the CPU".to_string());
+        }
+        if parsed.mode == FractalMode::Buddhabrot && (iters_given || parsed.palette.is_some() || parsed.subdivide) {
+            return Err("--iters, --palette and --subdivide only apply to the Mandelbrot; the Buddhabrot uses --bands".to_string());
+        }
+
+        Ok(parsed)
+    }
+}
+
+// Largest accepted --size side, which keeps a full RGBA8 image at 4 GiB
+const MAX_SIZE: u32 = 32768;
+
+fn parse_size(value: &str) -> Result<PhysicalSize<u32>, String> {
+    let err = || format!("--size expects WIDTHxHEIGHT, got {value}");
+    let (w, h) = value.split_once(['x', 'X']).ok_or_else(err)?;
+    let w: u32 = w.parse().map_err(|_| err())?;
+    let h: u32 = h.parse().map_err(|_| err())?;
+    if w == 0 || h == 0 {
+        return Err(err());
+    }
+    if w > MAX_SIZE || h > MAX_SIZE {
+        return Err(format!("--size {value} is too large, each side can be at most {MAX_SIZE}"));
+    }
+    Ok(PhysicalSize::new(w, h))
+}
+
+fn parse_bands(value: &str) -> Result<[u32; 3], String> {
+    let err = || format!("--bands expects three iteration limits r,g,b, got {value}");
+    let bands = value
+        .split(',')
+        .map(|v| v.trim().parse::<u32>().ok().filter(|n| *n > 0))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(err)?;
+    bands.try_into().map_err(|_| err())
+}
+
+// `render-fractal` subcommand: renders without opening a window
+pub fn render_fractal(args: &RenderArgs) -> Result<(), String> {
+    let start = Instant::now();
+    let pixels = if args.gpu {
+        render_gpu(args)?
+    } else {
+        render_cpu(args)?
+    };
+
+    image::save_buffer(
+        &args.out,
+        &pixels,
+        args.size.width,
+        args.size.height,
+        image::ColorType::Rgba8,
+    )
+    .map_err(|err| format!("failed to write {}: {err}", args.out))?;
+
+    println!(
+        "Wrote {} ({}x{}) in {:.2?}",
+        args.out,
+        args.size.width,
+        args.size.height,
+        start.elapsed()
+    );
+    Ok(())
+}
+
+fn render_cpu(args: &RenderArgs) -> Result<Vec<u8>, String> {
+    let palette = match &args.palette {
+        Some(path) => Palette::load(path)?,
+        None => Palette::Cosine,
+    };
+
+    let pixels = match args.mode {
+        FractalMode::Mandelbrot => {
+            mandelbrot::render_rgba(&args.view, args.size, &palette, args.subdivide)
+        }
+        FractalMode::Buddhabrot => {
+            let params = BuddhabrotParams {
+                center: args.view.center,
+                width: args.view.width,
+                bands: args.bands,
+            };
+            let density = buddhabrot::render_density(&params, args.size, args.samples, 0);
+            buddhabrot::density_to_rgba(&density)
+        }
+    };
+    Ok(pixels)
+}
+
+fn render_gpu(args: &RenderArgs) -> Result<Vec<u8>, String> {
+    let mut headless = pollster::block_on(Headless::new(args.fallback_adapter))?;
+    println!("Using {} ({:?})", headless.info.name, headless.info.backend);
+
+    // The Mandelbrot shader writes palette bytes as is, like the CPU renderer; the
+    // Buddhabrot writes linear values that `density_to_rgba` encodes to sRGB
+    let format = match args.mode {
+        FractalMode::Mandelbrot => wgpu::TextureFormat::Rgba8Unorm,
+        FractalMode::Buddhabrot => {
+            BuddhabrotRenderer::density_buffer_size(&headless.device, args.size)?;
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        }
+    };
+    let target = headless.create_target(format, args.size)?;
+    let mut layers = LayerStack::default();
+    let frames = match args.mode {
+        FractalMode::Mandelbrot => {
+            let mut fractal = FractalRenderer::new(&headless.device, format, headless.pipeline_cache.get());
+            fractal.view = args.view;
+            layers.push(fractal, true);
+            1
+        }
+        FractalMode::Buddhabrot => {
+            let mut buddhabrot = BuddhabrotRenderer::new(
+                &headless.device,
+                format,
+                args.size.width,
+                args.size.height,
+                headless.pipeline_cache.get(),
+            );
+            buddhabrot.set_params(BuddhabrotParams {
+                center: args.view.center,
+                width: args.view.width,
+                bands: args.bands,
+            });
+            layers.push(buddhabrot, true);
+            // bounded by `RenderArgs::parse`
+            u32::try_from(args.samples.div_ceil(buddhabrot::SAMPLES_PER_FRAME)).unwrap()
+        }
+    };
+    let pixels = headless.render(&mut layers, &target, frames)?;
+    if let Err(err) = headless.pipeline_cache.save() {
+        println!("Failed to save pipeline cache: {err}");
+    }
+    Ok(pixels)
+}
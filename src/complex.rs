@@ -0,0 +1,799 @@
+use dashu_float::round::mode::HalfAway;
+use dashu_float::{DBig, FBig};
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Scalar backend for `Complex`. Implemented for f32, f64 and `BigFloat` so the
+// same fractal / orbit code can run at whatever precision the zoom depth needs.
+// `/` by zero panics for `BigFloat`; use `checked_div` where the divisor can be zero.
+pub trait Real:
+    Clone
+    + PartialOrd
+    + fmt::Debug
+    + fmt::Display
+    + FromStr
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    fn zero() -> Self;
+    fn one() -> Self;
+    // None for NaN and infinities, which `BigFloat` cannot hold
+    fn from_f64(v: f64) -> Option<Self>;
+    fn to_f64(&self) -> f64;
+    fn abs(&self) -> Self;
+    fn sqrt(&self) -> Self;
+    fn exp(&self) -> Self;
+    fn ln(&self) -> Self;
+    fn sin(&self) -> Self;
+    fn cos(&self) -> Self;
+    fn atan2(&self, x: &Self) -> Self;
+    // true for -0.0 too, so formatting and branch cuts keep the sign of zero
+    fn is_sign_negative(&self) -> bool;
+
+    fn checked_div(&self, rhs: &Self) -> Option<Self> {
+        (*rhs != Self::zero()).then(|| self.clone() / rhs.clone())
+    }
+}
+
+macro_rules! impl_real_for_float {
+    ($t:ty) => {
+        impl Real for $t {
+            fn zero() -> Self {
+                0.0
+            }
+            fn one() -> Self {
+                1.0
+            }
+            fn from_f64(v: f64) -> Option<Self> {
+                v.is_finite().then_some(v as $t)
+            }
+            fn to_f64(&self) -> f64 {
+                *self as f64
+            }
+            fn abs(&self) -> Self {
+                <$t>::abs(*self)
+            }
+            fn sqrt(&self) -> Self {
+                <$t>::sqrt(*self)
+            }
+            fn exp(&self) -> Self {
+                <$t>::exp(*self)
+            }
+            fn ln(&self) -> Self {
+                <$t>::ln(*self)
+            }
+            fn sin(&self) -> Self {
+                <$t>::sin(*self)
+            }
+            fn cos(&self) -> Self {
+                <$t>::cos(*self)
+            }
+            fn is_sign_negative(&self) -> bool {
+                <$t>::is_sign_negative(*self)
+            }
+            fn atan2(&self, x: &Self) -> Self {
+                <$t>::atan2(*self, *x)
+            }
+        }
+    };
+}
+
+impl_real_for_float!(f32);
+impl_real_for_float!(f64);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Complex<T = f64> {
+    pub re: T,
+    pub im: T,
+}
+
+impl<T: Real> Complex<T> {
+    pub fn new(re: T, im: T) -> Self {
+        Self { re, im }
+    }
+
+    pub fn zero() -> Self {
+        Self::new(T::zero(), T::zero())
+    }
+
+    pub fn one() -> Self {
+        Self::new(T::one(), T::zero())
+    }
+
+    pub fn i() -> Self {
+        Self::new(T::zero(), T::one())
+    }
+
+    pub fn from_polar(r: T, theta: T) -> Self {
+        Self::new(r.clone() * theta.cos(), r * theta.sin())
+    }
+
+    pub fn conj(&self) -> Self {
+        Self::new(self.re.clone(), -self.im.clone())
+    }
+
+    pub fn scale(&self, k: T) -> Self {
+        Self::new(self.re.clone() * k.clone(), self.im.clone() * k)
+    }
+
+    // |z|^2, the cheap escape test used by the iteration loops
+    pub fn norm_sqr(&self) -> T {
+        self.re.clone() * self.re.clone() + self.im.clone() * self.im.clone()
+    }
+
+    pub fn abs(&self) -> T {
+        // Scale by the larger component so big / tiny values don't over- or underflow
+        let (a, b) = (self.re.abs(), self.im.abs());
+        let (hi, lo) = if a >= b { (a, b) } else { (b, a) };
+        if hi == T::zero() {
+            return hi;
+        }
+        let q = lo / hi.clone();
+        hi * (T::one() + q.clone() * q).sqrt()
+    }
+
+    pub fn arg(&self) -> T {
+        self.im.atan2(&self.re)
+    }
+
+    pub fn exp(&self) -> Self {
+        Self::from_polar(self.re.exp(), self.im.clone())
+    }
+
+    // Principal branch
+    pub fn ln(&self) -> Self {
+        Self::new(self.abs().ln(), self.arg())
+    }
+
+    pub fn sqrt(&self) -> Self {
+        if self.is_zero() {
+            return Self::zero();
+        }
+        let two = T::one() + T::one();
+        let r = self.abs();
+        let re = ((r.clone() + self.re.clone()) / two.clone()).sqrt();
+        let im = ((r - self.re.clone()) / two).sqrt();
+        if self.im.is_sign_negative() {
+            Self::new(re, -im)
+        } else {
+            Self::new(re, im)
+        }
+    }
+
+    // Powers of zero are 1 for a zero exponent, 0 for a positive one and None otherwise;
+    // `powf` and `powc` follow the same rule, using the real part of a complex exponent
+    pub fn powi(&self, n: i32) -> Option<Self> {
+        let mut base = if n < 0 { Self::one().checked_div(self.clone())? } else { self.clone() };
+        let mut n = n.unsigned_abs();
+        let mut acc = Self::one();
+        while n > 0 {
+            if n & 1 == 1 {
+                acc = acc * base.clone();
+            }
+            base = base.clone() * base;
+            n >>= 1;
+        }
+        Some(acc)
+    }
+
+    pub fn powf(&self, e: T) -> Option<Self> {
+        if self.is_zero() {
+            return Self::zero_pow(&e, e == T::zero());
+        }
+        Some((self.ln().scale(e)).exp())
+    }
+
+    pub fn powc(&self, e: Self) -> Option<Self> {
+        if self.is_zero() {
+            return Self::zero_pow(&e.re, e.is_zero());
+        }
+        Some((self.ln() * e).exp())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.re == T::zero() && self.im == T::zero()
+    }
+
+    // 0^e, given the real part of `e` and whether `e` is zero
+    fn zero_pow(re: &T, exponent_zero: bool) -> Option<Self> {
+        if exponent_zero {
+            Some(Self::one())
+        } else if *re > T::zero() {
+            Some(Self::zero())
+        } else {
+            None
+        }
+    }
+
+    pub fn sin(&self) -> Self {
+        // sin(a + bi) = sin a cosh b + i cos a sinh b
+        let (ch, sh) = cosh_sinh(&self.im);
+        Self::new(self.re.sin() * ch, self.re.cos() * sh)
+    }
+
+    pub fn cos(&self) -> Self {
+        // cos(a + bi) = cos a cosh b - i sin a sinh b
+        let (ch, sh) = cosh_sinh(&self.im);
+        Self::new(self.re.cos() * ch, -(self.re.sin() * sh))
+    }
+
+    pub fn to_f64(&self) -> Complex<f64> {
+        Complex::new(self.re.to_f64(), self.im.to_f64())
+    }
+
+    // None if either part is NaN or infinite
+    pub fn from_f64(c: Complex<f64>) -> Option<Self> {
+        Some(Self::new(T::from_f64(c.re)?, T::from_f64(c.im)?))
+    }
+
+    // None when `rhs` is zero, where `/` panics for `BigFloat`
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        (!rhs.is_zero()).then(|| self / rhs)
+    }
+}
+
+fn cosh_sinh<T: Real>(x: &T) -> (T, T) {
+    let two = T::one() + T::one();
+    let ep = x.exp();
+    let em = T::one() / ep.clone();
+    ((ep.clone() + em.clone()) / two.clone(), (ep - em) / two)
+}
+
+impl<T: Real> Add for Complex<T> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl<T: Real> Sub for Complex<T> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl<T: Real> Mul for Complex<T> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Complex::new(
+            self.re.clone() * rhs.re.clone() - self.im.clone() * rhs.im.clone(),
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl<T: Real> Div for Complex<T> {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        // Smith's algorithm, avoids overflow in |rhs|^2
+        if rhs.re.abs() >= rhs.im.abs() {
+            let r = rhs.im.clone() / rhs.re.clone();
+            let d = rhs.re + r.clone() * rhs.im;
+            Complex::new(
+                (self.re.clone() + self.im.clone() * r.clone()) / d.clone(),
+                (self.im - self.re * r) / d,
+            )
+        } else {
+            let r = rhs.re.clone() / rhs.im.clone();
+            let d = rhs.re * r.clone() + rhs.im;
+            Complex::new(
+                (self.re.clone() * r.clone() + self.im.clone()) / d.clone(),
+                (self.im * r - self.re) / d,
+            )
+        }
+    }
+}
+
+impl<T: Real> Neg for Complex<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+impl<T: Real> fmt::Display for Complex<T> {
+    // Formats as `a+bi` / `a-bi`; a precision (`{:.12}`) applies to both parts
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let negative_im = self.im.is_sign_negative();
+        let im = if negative_im { -self.im.clone() } else { self.im.clone() };
+        let sign = if negative_im { '-' } else { '+' };
+        match f.precision() {
+            Some(p) => write!(f, "{:.*}{}{:.*}i", p, self.re, sign, p, im),
+            None => write!(f, "{}{}{}i", self.re, sign, im),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseComplexError(String);
+
+impl fmt::Display for ParseComplexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid complex number: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseComplexError {}
+
+impl<T: Real> FromStr for Complex<T> {
+    type Err = ParseComplexError;
+
+    // Accepts `x,y`, `a+bi`, `a-bi`, `bi`, `i` and plain reals
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseComplexError(s.to_string());
+        let parse = |v: &str| v.trim().parse::<T>().map_err(|_| err());
+        let s = s.trim();
+
+        if let Some((re, im)) = s.split_once(',') {
+            return Ok(Self::new(parse(re)?, parse(im)?));
+        }
+
+        let Some(body) = s.strip_suffix('i') else {
+            return Ok(Self::new(parse(s)?, T::zero()));
+        };
+
+        // Find the sign separating the parts, skipping a leading sign and exponents (1e-5)
+        let bytes = body.as_bytes();
+        let split = (1..bytes.len())
+            .rev()
+            .find(|&i| (bytes[i] == b'+' || bytes[i] == b'-') && !matches!(bytes[i - 1], b'e' | b'E'));
+
+        let parse_im = |v: &str| match v.trim() {
+            "" | "+" => Ok(T::one()),
+            "-" => Ok(-T::one()),
+            v => parse(v),
+        };
+
+        match split {
+            Some(i) => Ok(Self::new(parse(&body[..i])?, parse_im(&body[i..])?)),
+            None => Ok(Self::new(T::zero(), parse_im(body)?)),
+        }
+    }
+}
+
+// Nothing in the app zooms deep enough for `BigFloat` yet; the tests below cover it
+type Fb = FBig<HalfAway, 2>;
+
+static BIG_PRECISION: AtomicUsize = AtomicUsize::new(128);
+
+// Extra bits carried through the series evaluations below
+const GUARD_BITS: usize = 16;
+
+// Arbitrary precision binary float for deep zoom coordinates and reference orbits.
+// Values created from f64 or strings use the global precision (in bits); arithmetic
+// keeps the larger precision of its operands.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct BigFloat(Fb);
+
+impl BigFloat {
+    pub fn precision() -> usize {
+        BIG_PRECISION.load(Ordering::Relaxed)
+    }
+
+    pub fn set_precision(bits: usize) {
+        BIG_PRECISION.store(bits.max(24), Ordering::Relaxed);
+    }
+
+    pub fn with_precision(&self, bits: usize) -> Self {
+        BigFloat(self.0.clone().with_precision(bits).value())
+    }
+
+    fn wrap(v: Fb, bits: usize) -> Self {
+        BigFloat(v.with_precision(bits).value())
+    }
+
+    fn bits(&self) -> usize {
+        self.0.precision().max(Self::precision())
+    }
+
+    fn pi(bits: usize) -> Fb {
+        // Machin: pi = 16 atan(1/5) - 4 atan(1/239)
+        let one = Fb::ONE.with_precision(bits).value();
+        let a = atan_series(&(one.clone() / Fb::from(5u8)), bits);
+        let b = atan_series(&(one / Fb::from(239u8)), bits);
+        a * Fb::from(16u8) - b * Fb::from(4u8)
+    }
+
+    fn sin_cos(&self) -> (Fb, Fb) {
+        let bits = self.bits() + GUARD_BITS;
+        let x = self.0.clone().with_precision(bits).value();
+        if x == Fb::ZERO {
+            return (x, Fb::ONE.with_precision(bits).value());
+        }
+
+        // Reduce to [-pi, pi], then halve a few more times and rebuild with double angles
+        let tau = Self::pi(bits) * Fb::from(2u8);
+        let k = Fb::from((x.clone() / tau.clone()).to_int().value());
+        let r = x - k * tau;
+        let halvings = 8;
+        let r = r >> halvings;
+
+        let r2 = r.clone() * r.clone();
+        let mut s = r.clone();
+        let mut term = r;
+        let mut n = 1u32;
+        while !negligible(&term, bits) {
+            term = -(term * r2.clone()) / Fb::from((n + 1) * (n + 2));
+            s += term.clone();
+            n += 2;
+        }
+        let mut c = (Fb::ONE - s.clone() * s.clone()).with_precision(bits).value();
+        c = sqrt(&c);
+
+        for _ in 0..halvings {
+            let (s2, c2) = (
+                Fb::from(2u8) * s.clone() * c.clone(),
+                c.clone() * c.clone() - s.clone() * s.clone(),
+            );
+            s = s2;
+            c = c2;
+        }
+        (s, c)
+    }
+}
+
+fn negligible(v: &Fb, bits: usize) -> bool {
+    *v == Fb::ZERO || v.repr().exponent() + (v.repr().digits() as isize) < -(bits as isize)
+}
+
+fn sqrt(v: &Fb) -> Fb {
+    dashu_base::SquareRoot::sqrt(v)
+}
+
+// Taylor series for |x| <= 1/5, fast enough for Machin's formula
+fn atan_series(x: &Fb, bits: usize) -> Fb {
+    let x2 = x.clone() * x.clone();
+    let mut power = x.clone();
+    let mut sum = x.clone();
+    let mut n = 1u32;
+    while !negligible(&power, bits) {
+        power = -(power * x2.clone());
+        n += 2;
+        sum += power.clone() / Fb::from(n);
+    }
+    sum
+}
+
+fn atan(x: &Fb, bits: usize) -> Fb {
+    if x.repr().is_zero() {
+        return x.clone();
+    }
+    if dashu_base::Abs::abs(x.clone()) > Fb::ONE {
+        let half_pi = BigFloat::pi(bits) / Fb::from(2u8);
+        let inv = atan(&(Fb::ONE.with_precision(bits).value() / x.clone()), bits);
+        return if *x > Fb::ZERO { half_pi - inv } else { -half_pi - inv };
+    }
+    // atan(x) = 2 atan(x / (1 + sqrt(1 + x^2))), applied until the series converges quickly
+    let mut x = x.clone().with_precision(bits).value();
+    let fifth = Fb::ONE.with_precision(bits).value() / Fb::from(5u8);
+    let mut doublings = 0;
+    while dashu_base::Abs::abs(x.clone()) > fifth {
+        x = x.clone() / (Fb::ONE + sqrt(&(Fb::ONE + x.clone() * x)));
+        doublings += 1;
+    }
+    atan_series(&x, bits) << doublings
+}
+
+impl Real for BigFloat {
+    fn zero() -> Self {
+        BigFloat::wrap(Fb::ZERO, Self::precision())
+    }
+
+    fn one() -> Self {
+        BigFloat::wrap(Fb::ONE, Self::precision())
+    }
+
+    fn from_f64(v: f64) -> Option<Self> {
+        // dashu maps infinities to its own infinity, which no operation accepts
+        let v = Fb::try_from(v).ok().filter(|_| v.is_finite())?;
+        Some(BigFloat::wrap(v, Self::precision()))
+    }
+
+    fn to_f64(&self) -> f64 {
+        self.0.to_f64().value()
+    }
+
+    fn abs(&self) -> Self {
+        BigFloat(dashu_base::Abs::abs(self.0.clone()))
+    }
+
+    fn sqrt(&self) -> Self {
+        BigFloat(sqrt(&self.0))
+    }
+
+    fn exp(&self) -> Self {
+        BigFloat(self.0.exp())
+    }
+
+    fn ln(&self) -> Self {
+        BigFloat(self.0.ln())
+    }
+
+    fn sin(&self) -> Self {
+        BigFloat::wrap(self.sin_cos().0, self.bits())
+    }
+
+    fn cos(&self) -> Self {
+        BigFloat::wrap(self.sin_cos().1, self.bits())
+    }
+
+    // `FBig` has no negative zero
+    fn is_sign_negative(&self) -> bool {
+        *self < Self::zero()
+    }
+
+    fn atan2(&self, x: &Self) -> Self {
+        let out_bits = self.bits().max(x.bits());
+        let bits = out_bits + GUARD_BITS;
+        let (y, x) = (&self.0, &x.0);
+        let result = if *x > Fb::ZERO {
+            atan(&(y.clone().with_precision(bits).value() / x.clone()), bits)
+        } else if *x < Fb::ZERO {
+            let a = atan(&(y.clone().with_precision(bits).value() / x.clone()), bits);
+            if *y >= Fb::ZERO {
+                a + BigFloat::pi(bits)
+            } else {
+                a - BigFloat::pi(bits)
+            }
+        } else if *y > Fb::ZERO {
+            BigFloat::pi(bits) / Fb::from(2u8)
+        } else if *y < Fb::ZERO {
+            -BigFloat::pi(bits) / Fb::from(2u8)
+        } else {
+            Fb::ZERO
+        };
+        BigFloat::wrap(result, out_bits)
+    }
+}
+
+macro_rules! impl_big_op {
+    ($tr:ident, $method:ident, $op:tt) => {
+        impl $tr for BigFloat {
+            type Output = Self;
+
+            fn $method(self, rhs: Self) -> Self::Output {
+                BigFloat(self.0 $op rhs.0)
+            }
+        }
+    };
+}
+
+impl_big_op!(Add, add, +);
+impl_big_op!(Sub, sub, -);
+impl_big_op!(Mul, mul, *);
+impl_big_op!(Div, div, /);
+
+impl Neg for BigFloat {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        BigFloat(-self.0)
+    }
+}
+
+impl FromStr for BigFloat {
+    type Err = ParseComplexError;
+
+    // Decimal input, converted to binary at the global precision
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bits = Self::precision();
+        let decimal = DBig::from_str(s.trim()).map_err(|_| ParseComplexError(s.to_string()))?;
+        // Enough decimal digits that the base conversion doesn't lose bits
+        let digits = (bits * 3 / 10 + 2).max(decimal.precision());
+        let binary = decimal
+            .with_precision(digits)
+            .value()
+            .with_base::<2>()
+            .value();
+        Ok(BigFloat::wrap(binary, bits))
+    }
+}
+
+impl fmt::Display for BigFloat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Significant decimal digits matching the binary precision
+        let digits = self.0.precision() * 3 / 10 + 1;
+        let decimal = self.0.to_decimal().value().with_precision(digits).value();
+        match f.precision() {
+            Some(p) => write!(f, "{:.*}", p, decimal),
+            None => write!(f, "{}", decimal),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{E, FRAC_PI_2, PI};
+
+    fn c<T: Real>(re: f64, im: f64) -> Complex<T> {
+        Complex::from_f64(Complex::new(re, im)).unwrap()
+    }
+
+    fn assert_close<T: Real>(actual: Complex<T>, expected: Complex<f64>, tolerance: f64) {
+        let actual = actual.to_f64();
+        let scale = expected.abs().max(1.0);
+        assert!(
+            (actual - expected).abs() <= tolerance * scale,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    // Checks the arithmetic and functions of one backend against f64 results
+    fn check_backend<T: Real>(tolerance: f64) {
+        let a = c::<T>(3.0, -4.0);
+        let b = c::<T>(-1.5, 2.0);
+        assert_close(a.clone() + b.clone(), Complex::new(1.5, -2.0), tolerance);
+        assert_close(a.clone() - b.clone(), Complex::new(4.5, -6.0), tolerance);
+        assert_close(a.clone() * b.clone(), Complex::new(3.5, 12.0), tolerance);
+        assert_close(-a.clone(), Complex::new(-3.0, 4.0), tolerance);
+
+        // Smith's algorithm branches on the larger part of the divisor
+        assert_close(a.clone() / c(2.0, 1.0), Complex::new(0.4, -2.2), tolerance);
+        assert_close(a.clone() / c(1.0, 2.0), Complex::new(-1.0, -2.0), tolerance);
+        assert_close(a.clone() / c(0.0, 2.0), Complex::new(-2.0, -1.5), tolerance);
+        assert_close(a.clone() / c(-2.0, 0.0), Complex::new(-1.5, 2.0), tolerance);
+        assert_close((a.clone() / b.clone()) * b.clone(), a.to_f64(), tolerance);
+
+        assert_close(a.conj(), Complex::new(3.0, 4.0), tolerance);
+        assert!((a.abs().to_f64() - 5.0).abs() <= tolerance * 5.0);
+        assert!((a.norm_sqr().to_f64() - 25.0).abs() <= tolerance * 25.0);
+        assert!((c::<T>(0.0, -1.0).arg().to_f64() + FRAC_PI_2).abs() <= tolerance);
+
+        assert_close(c::<T>(1.0, 0.0).exp(), Complex::new(E, 0.0), tolerance);
+        assert_close(c::<T>(0.0, PI).exp(), Complex::new(-1.0, 0.0), tolerance);
+        assert_close(c::<T>(-1.0, 0.0).ln(), Complex::new(0.0, PI), tolerance);
+        assert_close(c::<T>(0.0, 1.0).ln(), Complex::new(0.0, FRAC_PI_2), tolerance);
+        // inside the principal branch, |im| < pi
+        assert_close(b.exp().ln(), b.to_f64(), tolerance);
+        assert_close(c::<T>(-4.0, 0.0).sqrt(), Complex::new(0.0, 2.0), tolerance);
+        assert_close(c::<T>(0.0, -2.0).sqrt(), Complex::new(1.0, -1.0), tolerance);
+
+        assert_close(c::<T>(1.0, 1.0).powi(2).unwrap(), Complex::new(0.0, 2.0), tolerance);
+        assert_close(c::<T>(1.0, 1.0).powi(-2).unwrap(), Complex::new(0.0, -0.5), tolerance);
+        assert_close(c::<T>(2.0, 0.0).powi(0).unwrap(), Complex::new(1.0, 0.0), tolerance);
+        assert!(Complex::<T>::zero().powi(-1).is_none());
+        assert_close(c::<T>(-1.0, 0.0).powf(c::<T>(0.5, 0.0).re).unwrap(), Complex::new(0.0, 1.0), tolerance);
+        // i^i = e^(-pi/2)
+        assert_close(
+            Complex::<T>::i().powc(Complex::i()).unwrap(),
+            Complex::new((-FRAC_PI_2).exp(), 0.0),
+            tolerance,
+        );
+
+        // powers of zero agree between the three
+        let zero = Complex::<T>::zero();
+        let real = |v: f64| T::from_f64(v).unwrap();
+        for (n, expected) in [(2, Some(0.0)), (0, Some(1.0)), (-1, None)] {
+            let expected = expected.map(|re| Complex::new(re, 0.0));
+            assert_eq!(zero.powi(n).map(|z| z.to_f64()), expected, "0^{n}");
+            assert_eq!(zero.powf(real(n as f64)).map(|z| z.to_f64()), expected, "0^{n}.0");
+            assert_eq!(zero.powc(c(n as f64, 0.0)).map(|z| z.to_f64()), expected, "0^({n}+0i)");
+        }
+        assert_eq!(zero.powc(c(0.5, -3.0)).map(|z| z.to_f64()), Some(Complex::new(0.0, 0.0)));
+        assert!(zero.powc(c(0.0, 1.0)).is_none());
+        assert!(zero.powc(c(-0.5, 1.0)).is_none());
+
+        assert_close(c::<T>(FRAC_PI_2, 0.0).sin(), Complex::new(1.0, 0.0), tolerance);
+        assert_close(c::<T>(PI, 0.0).cos(), Complex::new(-1.0, 0.0), tolerance);
+        // sin(i) = i sinh 1, cos(i) = cosh 1
+        assert_close(Complex::<T>::i().sin(), Complex::new(0.0, 1f64.sinh()), tolerance);
+        assert_close(Complex::<T>::i().cos(), Complex::new(1f64.cosh(), 0.0), tolerance);
+        let z = c::<T>(0.7, -0.3);
+        let (s, co) = (z.sin(), z.cos());
+        assert_close(s.clone() * s + co.clone() * co, Complex::new(1.0, 0.0), tolerance);
+
+        assert!(a.clone().checked_div(Complex::zero()).is_none());
+        assert_close(a.checked_div(b).unwrap(), Complex::new(-2.0, 0.0), tolerance);
+    }
+
+    #[test]
+    fn f32_backend() {
+        check_backend::<f32>(1e-5);
+    }
+
+    #[test]
+    fn f64_backend() {
+        check_backend::<f64>(1e-12);
+        // -0 in the imaginary part picks the lower side of the branch cut
+        assert_eq!(Complex::new(-4.0, -0.0).sqrt(), Complex::new(0.0, -2.0));
+        assert_eq!(Complex::new(-4.0, 0.0).sqrt(), Complex::new(0.0, 2.0));
+    }
+
+    #[test]
+    fn big_float_backend() {
+        check_backend::<BigFloat>(1e-14);
+    }
+
+    #[test]
+    fn huge_and_tiny_operands() {
+        // |rhs|^2 would overflow / underflow without Smith's algorithm
+        let big = Complex::new(1e300, 1e300);
+        assert_close(big / Complex::new(1e300, 2e300), Complex::new(0.6, -0.2), 1e-15);
+        assert_close(big / Complex::new(2e300, 1e300), Complex::new(0.6, 0.2), 1e-15);
+        let tiny = Complex::new(1e-300, -1e-300);
+        assert_close(tiny / Complex::new(0.0, 1e-300), Complex::new(-1.0, -1.0), 1e-15);
+        assert!((big.abs() / 1e300 - 2f64.sqrt()).abs() < 1e-15);
+        assert!((Complex::new(3e-310, 4e-310).abs() / 5e-310 - 1.0).abs() < 1e-9);
+        // the naive sqrt(norm_sqr) overflows f32 here
+        assert!((Complex::new(1.5e38f32, 2e38).abs() / 2.5e38 - 1.0).abs() < 1e-6);
+
+        // beyond f64's exponent range
+        let huge: BigFloat = "1e400".parse().unwrap();
+        let z = Complex::new(huge.clone(), huge.clone());
+        let q = z.clone() / Complex::new(huge.clone(), -huge.clone());
+        assert_close(q, Complex::new(0.0, 1.0), 1e-30);
+        assert_close(z.clone() * z.conj() / Complex::new(huge.clone() * huge, BigFloat::zero()), Complex::new(2.0, 0.0), 1e-30);
+        let tiny: BigFloat = "1e-400".parse().unwrap();
+        let w = Complex::new(BigFloat::one(), tiny.clone());
+        assert_close((w - Complex::one()) / Complex::new(BigFloat::zero(), tiny), Complex::new(1.0, 0.0), 1e-30);
+    }
+
+    #[test]
+    fn non_finite_values() {
+        assert!(f64::from_f64(f64::NAN).is_none());
+        assert!(f32::from_f64(f64::INFINITY).is_none());
+        assert!(BigFloat::from_f64(f64::NAN).is_none());
+        assert!(BigFloat::from_f64(f64::NEG_INFINITY).is_none());
+        assert!(Complex::<BigFloat>::from_f64(Complex::new(1.0, f64::INFINITY)).is_none());
+        assert_eq!(BigFloat::from_f64(0.5).unwrap().to_f64(), 0.5);
+        assert!(BigFloat::one().checked_div(&BigFloat::zero()).is_none());
+        assert_eq!(BigFloat::one().checked_div(&BigFloat::from_f64(4.0).unwrap()).unwrap().to_f64(), 0.25);
+        assert!(Complex::<BigFloat>::one().checked_div(Complex::zero()).is_none());
+    }
+
+    #[test]
+    fn parse() {
+        let parse = |s: &str| s.parse::<Complex>().unwrap();
+        assert_eq!(parse("-0.75,0.1"), Complex::new(-0.75, 0.1));
+        assert_eq!(parse(" 1.5 , -2 "), Complex::new(1.5, -2.0));
+        assert_eq!(parse("1+2i"), Complex::new(1.0, 2.0));
+        assert_eq!(parse("1-2i"), Complex::new(1.0, -2.0));
+        assert_eq!(parse("-0.5-0.25i"), Complex::new(-0.5, -0.25));
+        assert_eq!(parse("-i"), Complex::new(0.0, -1.0));
+        assert_eq!(parse("i"), Complex::new(0.0, 1.0));
+        assert_eq!(parse("3+i"), Complex::new(3.0, 1.0));
+        assert_eq!(parse("2.5i"), Complex::new(0.0, 2.5));
+        assert_eq!(parse("1e-5-2i"), Complex::new(1e-5, -2.0));
+        assert_eq!(parse("1E+3+1e-3i"), Complex::new(1e3, 1e-3));
+        assert_eq!(parse("-7"), Complex::new(-7.0, 0.0));
+        for bad in ["", "x", "1+", "1+2j", "1,2,3", "i1"] {
+            assert!(bad.parse::<Complex>().is_err(), "{bad:?} parsed");
+        }
+
+        let big: Complex<BigFloat> = "0.1-0.2i".parse().unwrap();
+        assert_close(big, Complex::new(0.1, -0.2), 1e-15);
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for z in [
+            Complex::new(1.0, 2.0),
+            Complex::new(-0.75, -0.1),
+            Complex::new(1e-5, -2.0),
+            Complex::new(0.0, 0.0),
+            Complex::new(-1e300, 3.25e-200),
+            Complex::new(1.0, 0.0).conj(),
+            Complex::new(-0.0, -0.0),
+        ] {
+            let parsed = z.to_string().parse::<Complex>().unwrap();
+            assert_eq!(parsed, z);
+            assert_eq!(parsed.im.is_sign_negative(), z.im.is_sign_negative(), "{z}");
+        }
+        assert_eq!(Complex::new(1.0, 0.0).conj().to_string(), "1-0i");
+        assert_eq!(format!("{:.2}", Complex::new(1.0, -0.5)), "1.00-0.50i");
+        let big: Complex<BigFloat> = "-0.7453+0.1127i".parse().unwrap();
+        assert_eq!(big.to_string().parse::<Complex<BigFloat>>().unwrap(), big);
+    }
+}
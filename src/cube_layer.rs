@@ -0,0 +1,280 @@
+use crate::camera::{CameraBuffer, CameraUniforms};
+use crate::layer::{FrameContext, Layer, RenderTarget, DEPTH_FORMAT};
+use crate::uniform;
+use egui_wgpu::wgpu;
+use egui_wgpu::wgpu::util::DeviceExt;
+use glam::{Mat4, Quat, Vec3};
+use std::any::Any;
+
+const MAX_GRID: u32 = 16;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CubeVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+}
+
+impl CubeVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<CubeVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CubeInstance {
+    model: Mat4,
+    color: [f32; 4],
+}
+
+impl CubeInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        2 => Float32x4,
+        3 => Float32x4,
+        4 => Float32x4,
+        5 => Float32x4,
+        6 => Float32x4,
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<CubeInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Unit cube centered on the origin, two counter-clockwise triangles per face
+fn cube_vertices() -> Vec<CubeVertex> {
+    // (normal, u, v) with u x v = normal
+    let faces = [
+        (Vec3::X, Vec3::Y, Vec3::Z),
+        (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+        (Vec3::Y, Vec3::Z, Vec3::X),
+        (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::Y, Vec3::X),
+    ];
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+    faces
+        .iter()
+        .flat_map(|&(normal, u, v)| {
+            corners.iter().map(move |&(a, b)| CubeVertex {
+                position: ((normal + u * a + v * b) * 0.5).to_array(),
+                normal: normal.to_array(),
+            })
+        })
+        .collect()
+}
+
+// Depth-tested demo scene: a grid of spinning cubes on a floor, seen through
+// the shared camera
+pub struct CubeLayer {
+    grid: u32,
+    spin: bool,
+    camera: CameraBuffer,
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+    instance_buffer: wgpu::Buffer,
+    instance_count: u32,
+    format: wgpu::TextureFormat,
+    cache: Option<wgpu::PipelineCache>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl CubeLayer {
+    pub fn new(device: &wgpu::Device, target_format: wgpu::TextureFormat, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let source = include_str!("cube_shader.wgsl");
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Cube Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        uniform::check_layout::<CameraUniforms>("cube_shader.wgsl", source, 0, 0);
+        let camera = CameraBuffer::new(device);
+
+        let vertices = cube_vertices();
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cube Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        // every grid cell plus the floor
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cube Instance Buffer"),
+            size: ((MAX_GRID * MAX_GRID + 1) as usize * std::mem::size_of::<CubeInstance>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cube Pipeline Layout"),
+            bind_group_layouts: &[&camera.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, &shader, target_format, 1, cache);
+
+        Self {
+            grid: 5,
+            spin: true,
+            camera,
+            vertex_buffer,
+            vertex_count: vertices.len() as u32,
+            instance_buffer,
+            instance_count: 0,
+            format: target_format,
+            cache: cache.cloned(),
+            shader,
+            pipeline_layout,
+            pipeline,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Cube Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[CubeVertex::desc(), CubeInstance::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache,
+        })
+    }
+
+    fn instances(&self, time: f32) -> Vec<CubeInstance> {
+        let grid = self.grid as f32;
+        let spacing = 2.0 / grid;
+        let size = spacing * 0.6;
+        let mut instances = vec![CubeInstance {
+            model: Mat4::from_scale_rotation_translation(
+                Vec3::new(2.4, 0.05, 2.4),
+                Quat::IDENTITY,
+                Vec3::new(0.0, -0.025, 0.0),
+            ),
+            color: [0.6, 0.6, 0.6, 1.0],
+        }];
+        for z in 0..self.grid {
+            for x in 0..self.grid {
+                let (u, v) = ((x as f32 + 0.5) / grid, (z as f32 + 0.5) / grid);
+                let angle = if self.spin { time * (0.5 + u) + v * 3.0 } else { 0.0 };
+                let position = Vec3::new((u - 0.5) * 2.0, size * 0.5 + 0.2 * (angle * 0.7).sin().abs(), (v - 0.5) * 2.0);
+                instances.push(CubeInstance {
+                    model: Mat4::from_scale_rotation_translation(
+                        Vec3::splat(size),
+                        Quat::from_rotation_y(angle) * Quat::from_rotation_x(angle * 0.5),
+                        position,
+                    ),
+                    color: [0.2 + 0.8 * u, 0.3, 0.2 + 0.8 * v, 1.0],
+                });
+            }
+        }
+        instances
+    }
+}
+
+impl Layer for CubeLayer {
+    fn name(&self) -> &str {
+        "Cubes"
+    }
+
+    fn restore(&mut self, old: Box<dyn Any>) {
+        let Ok(old) = old.downcast::<Self>() else {
+            return;
+        };
+        self.grid = old.grid;
+        self.spin = old.spin;
+    }
+
+    fn animating(&self) -> bool {
+        self.spin
+    }
+
+    fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.pipeline = Self::create_pipeline(device, &self.pipeline_layout, &self.shader, self.format, sample_count, self.cache.as_ref());
+    }
+
+    fn uses_depth(&self) -> bool {
+        true
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        self.camera.update(frame.queue, frame.camera, frame.size);
+        let instances = self.instances(frame.time);
+        frame.queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+        self.instance_count = instances.len() as u32;
+    }
+
+    fn render(&mut self, _frame: &FrameContext, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget) {
+        // the stack provides depth whenever this layer is enabled
+        let Some(depth_attachment) = target.depth_attachment() else {
+            return;
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Cube Pass"),
+            color_attachments: &[Some(target.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: Some(depth_attachment),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.camera.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..self.instance_count);
+    }
+
+    fn metadata(&self) -> Option<String> {
+        Some(format!("grid {}x{}, spin {}", self.grid, self.grid, self.spin))
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.grid, 1..=MAX_GRID).text("Grid"));
+        ui.checkbox(&mut self.spin, "Animate");
+    }
+}
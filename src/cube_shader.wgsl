@@ -0,0 +1,58 @@
+// cube_shader.wgsl
+struct Camera {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    view_projection: mat4x4<f32>,
+    eye: vec3<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+};
+
+// Model matrix columns and a linear RGB color per cube
+struct InstanceInput {
+    @location(2) model_0: vec4<f32>,
+    @location(3) model_1: vec4<f32>,
+    @location(4) model_2: vec4<f32>,
+    @location(5) model_3: vec4<f32>,
+    @location(6) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec3<f32>,
+};
+
+const LIGHT_DIR: vec3<f32> = vec3<f32>(0.4, 0.8, 0.3);
+
+@vertex
+fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let world = model * vec4<f32>(vertex.position, 1.0);
+    var out: VertexOutput;
+    out.clip_position = camera.view_projection * world;
+    out.world_position = world.xyz;
+    // fine for the rotations and axis-aligned scales used here
+    out.normal = (model * vec4<f32>(vertex.normal, 0.0)).xyz;
+    out.color = instance.color.rgb;
+    return out;
+}
+
+// Lambert plus a little Blinn-Phong highlight
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let n = normalize(in.normal);
+    let l = normalize(LIGHT_DIR);
+    let v = normalize(camera.eye - in.world_position);
+    let h = normalize(l + v);
+    let diffuse = max(dot(n, l), 0.0);
+    let specular = pow(max(dot(n, h), 0.0), 32.0) * 0.3;
+    return vec4<f32>(in.color * (0.15 + 0.85 * diffuse) + specular, 1.0);
+}
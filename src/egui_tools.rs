@@ -2,13 +2,26 @@ use egui::Context;
 use egui_wgpu::wgpu::{CommandEncoder, Device, Queue, StoreOp, TextureFormat, TextureView};
 use egui_wgpu::{wgpu, Renderer, ScreenDescriptor};
 use egui_winit::State;
+use std::time::{Duration, Instant};
 use winit::event::WindowEvent;
 use winit::window::Window;
 
+// What the app learns from finishing an egui frame
+pub struct EguiFrameOutput {
+    pub tessellation: Duration,
+    // how soon egui wants the next frame; `Duration::MAX` if only input should cause one
+    pub repaint_delay: Duration,
+}
+
 pub struct EguiRenderer {
     state: State,
     renderer: Renderer,
     frame_started: bool,
+    output_color_format: TextureFormat,
+    output_depth_format: Option<TextureFormat>,
+    msaa_samples: u32,
+    // multisampled target resolved into the surface, with its size in pixels
+    msaa_target: Option<([u32; 2], TextureView)>,
 }
 
 impl EguiRenderer {
@@ -16,6 +29,10 @@ impl EguiRenderer {
         self.state.egui_ctx()
     }
 
+    pub fn renderer_mut(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+
     pub fn new(
         device: &Device,
         output_color_format: TextureFormat,
@@ -45,11 +62,88 @@ impl EguiRenderer {
             state: egui_state,
             renderer: egui_renderer,
             frame_started: false,
+            output_color_format,
+            output_depth_format,
+            msaa_samples,
+            msaa_target: None,
         }
     }
 
-    pub fn handle_input(&mut self, window: &Window, event: &WindowEvent) {
-        let _ = self.state.on_window_event(window, event);
+    pub fn msaa_samples(&self) -> u32 {
+        self.msaa_samples
+    }
+
+    pub fn output_color_format(&self) -> TextureFormat {
+        self.output_color_format
+    }
+
+    // The sample count is baked into the renderer's pipeline, so this replaces the
+    // renderer. Native textures have to be registered again afterwards.
+    pub fn set_msaa_samples(&mut self, device: &Device, queue: &Queue, msaa_samples: u32) {
+        self.msaa_samples = msaa_samples;
+        self.recreate_renderer(device, queue);
+    }
+
+    // Same as `set_msaa_samples`, for the format of the view egui draws into
+    pub fn set_output_color_format(&mut self, device: &Device, queue: &Queue, format: TextureFormat) {
+        self.output_color_format = format;
+        self.recreate_renderer(device, queue);
+    }
+
+    fn recreate_renderer(&mut self, device: &Device, queue: &Queue) {
+        self.renderer = Renderer::new(
+            device,
+            self.output_color_format,
+            self.output_depth_format,
+            self.msaa_samples,
+            true,
+        );
+        self.msaa_target = None;
+        self.upload_fonts(device, queue);
+    }
+
+    // Keeps the egui context and input state of a renderer from a previous device,
+    // so window positions and other UI memory survive the switch
+    pub fn adopt_state(&mut self, device: &Device, queue: &Queue, old: EguiRenderer) {
+        self.state = old.state;
+        self.upload_fonts(device, queue);
+    }
+
+    // egui only sends texture deltas on change, so hand the font atlas over again
+    fn upload_fonts(&mut self, device: &Device, queue: &Queue) {
+        let font_image = self.context().fonts(|fonts| fonts.image());
+        let delta = egui::epaint::ImageDelta::full(font_image, egui::epaint::TextureAtlas::texture_options());
+        self.renderer
+            .update_texture(device, queue, egui::TextureId::default(), &delta);
+    }
+
+    fn msaa_view(&mut self, device: &Device, size: [u32; 2]) -> Option<&TextureView> {
+        if self.msaa_samples == 1 {
+            return None;
+        }
+        if self.msaa_target.as_ref().is_none_or(|(target_size, _)| *target_size != size) {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("egui MSAA Texture"),
+                size: wgpu::Extent3d {
+                    width: size[0],
+                    height: size[1],
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: self.msaa_samples,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.output_color_format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            self.msaa_target = Some((size, view));
+        }
+        self.msaa_target.as_ref().map(|(_, view)| view)
+    }
+
+    pub fn handle_input(&mut self, window: &Window, event: &WindowEvent) -> egui_winit::EventResponse {
+        self.state.on_window_event(window, event)
     }
 
     pub fn ppp(&mut self, v: f32) {
@@ -70,7 +164,7 @@ impl EguiRenderer {
         window: &Window,
         window_surface_view: &TextureView,
         screen_descriptor: ScreenDescriptor,
-    ) {
+    ) -> EguiFrameOutput {
         if !self.frame_started {
             panic!("begin_frame must be called before end_frame_and_draw can be called!");
         }
@@ -81,24 +175,36 @@ impl EguiRenderer {
 
         self.state
             .handle_platform_output(window, full_output.platform_output);
+        let repaint_delay = full_output
+            .viewport_output
+            .get(&egui::ViewportId::ROOT)
+            .map_or(Duration::MAX, |output| output.repaint_delay);
 
+        let tessellation_start = Instant::now();
         let tris = self
             .state
             .egui_ctx()
             .tessellate(full_output.shapes, self.state.egui_ctx().pixels_per_point());
+        let tessellation = tessellation_start.elapsed();
         for (id, image_delta) in &full_output.textures_delta.set {
             self.renderer
                 .update_texture(device, queue, *id, image_delta);
         }
         self.renderer
             .update_buffers(device, queue, encoder, &tris, &screen_descriptor);
+        // With MSAA only the resolved surface is needed afterwards
+        let (view, resolve_target, store) = match self.msaa_view(device, screen_descriptor.size_in_pixels) {
+            Some(msaa_view) => (msaa_view, Some(window_surface_view), StoreOp::Discard),
+            None => (window_surface_view, None, StoreOp::Store),
+        };
         let rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: window_surface_view,
-                resolve_target: None,
+                view,
+                resolve_target,
                 ops: egui_wgpu::wgpu::Operations {
-                    load: egui_wgpu::wgpu::LoadOp::Load,
-                    store: StoreOp::Store,
+                    // the scene reaches the screen as an egui image, so egui owns the whole surface
+                    load: egui_wgpu::wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store,
                 },
             })],
             depth_stencil_attachment: None,
@@ -114,5 +220,9 @@ impl EguiRenderer {
         }
 
         self.frame_started = false;
+        EguiFrameOutput {
+            tessellation,
+            repaint_delay,
+        }
     }
 }
\ No newline at end of file
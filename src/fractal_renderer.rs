@@ -0,0 +1,226 @@
+use crate::layer::{FrameContext, Layer, LayerInput, RenderTarget};
+use crate::mandelbrot::{FractalPlot, FractalView};
+use crate::orbit_overlay::OrbitInspector;
+use crate::uniform::{self, impl_uniform, UniformBuffer};
+use egui_wgpu::wgpu;
+use std::any::Any;
+use winit::dpi::PhysicalSize;
+use winit::event::MouseButton;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FractalUniforms {
+    origin: [f32; 2],
+    inc: f32,
+    max_iter: u32,
+}
+
+impl_uniform!(FractalUniforms { origin, inc, max_iter });
+
+// GPU counterpart of `mandelbrot::render_rgba` for the interactive view
+pub struct FractalRenderer {
+    pub view: FractalView,
+    pub inspector: OrbitInspector,
+    format: wgpu::TextureFormat,
+    cache: Option<wgpu::PipelineCache>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: UniformBuffer<FractalUniforms>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl FractalRenderer {
+    pub fn new(device: &wgpu::Device, target_format: wgpu::TextureFormat, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let source = include_str!("mandelbrot.wgsl");
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mandelbrot Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        uniform::check_layout::<FractalUniforms>("mandelbrot.wgsl", source, 0, 0);
+        let uniform_buffer = UniformBuffer::new(device, "Mandelbrot Uniform Buffer");
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("mandelbrot_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.binding(),
+            }],
+            label: Some("mandelbrot_bind_group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mandelbrot Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, &shader, target_format, 1, cache);
+
+        Self {
+            view: FractalView::default(),
+            inspector: OrbitInspector::new(),
+            format: target_format,
+            cache: cache.cloned(),
+            shader,
+            pipeline_layout,
+            pipeline,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mandelbrot Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache,
+        })
+    }
+
+    pub fn plot(&self, size: PhysicalSize<u32>) -> FractalPlot {
+        FractalPlot::new(self.view.center, self.view.width, size)
+    }
+
+    // Zooms by `factor` keeping the point under `pixel` fixed
+    pub fn zoom_at(&mut self, pixel: (f64, f64), factor: f64, size: PhysicalSize<u32>) {
+        let anchor = self.plot(size).get_point(pixel);
+        self.view.width *= factor;
+        let moved = self.plot(size).get_point(pixel);
+        self.view.center = self.view.center + (anchor - moved);
+    }
+
+    pub fn pan(&mut self, delta: (f64, f64), size: PhysicalSize<u32>) {
+        let plot = self.plot(size);
+        let offset = plot.get_point((0.0, 0.0)) - plot.get_point(delta);
+        self.view.center = self.view.center + offset;
+    }
+}
+
+impl Layer for FractalRenderer {
+    fn name(&self) -> &str {
+        "Mandelbrot"
+    }
+
+    fn restore(&mut self, old: Box<dyn Any>) {
+        let Ok(old) = old.downcast::<Self>() else {
+            return;
+        };
+        self.view = old.view;
+        self.inspector = old.inspector;
+    }
+
+    fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.pipeline = Self::create_pipeline(device, &self.pipeline_layout, &self.shader, self.format, sample_count, self.cache.as_ref());
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        let plot = self.plot(frame.size);
+        let origin = plot.get_point((0.0, 0.0));
+        let inc = plot.get_point((1.0, 0.0)).re - origin.re;
+        let uniforms = FractalUniforms {
+            origin: [origin.re as f32, origin.im as f32],
+            inc: inc as f32,
+            max_iter: self.view.max_iter,
+        };
+        self.uniform_buffer.write(frame.queue, uniforms);
+    }
+
+    fn render(&mut self, _frame: &FrameContext, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Mandelbrot Pass"),
+            color_attachments: &[Some(target.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.add(
+            egui::Slider::new(&mut self.view.max_iter, 16..=5000)
+                .logarithmic(true)
+                .text("Iterations"),
+        );
+        ui.label(format!("center {:.12}", self.view.center));
+        ui.label(format!("width {:e}", self.view.width));
+        if ui.button("Reset view").clicked() {
+            self.view = Default::default();
+        }
+        ui.separator();
+        self.inspector.ui(ui, self.view.max_iter);
+    }
+
+    fn metadata(&self) -> Option<String> {
+        Some(format!("{:?}", self.view))
+    }
+
+    fn overlay(&mut self, painter: &egui::Painter, frame: &FrameContext) {
+        let plot = self.plot(frame.size);
+        let hovered = frame.cursor.map(|(x, y)| plot.get_point((x as f64, y as f64)));
+        self.inspector.set_hovered(hovered);
+        self.inspector.paint(painter, &plot, frame.size, self.view.max_iter);
+    }
+
+    // Drag pans, scroll zooms around the cursor, right click pins the hovered orbit
+    fn input(&mut self, event: &LayerInput, size: PhysicalSize<u32>) -> bool {
+        match *event {
+            LayerInput::Drag { delta } => self.pan((delta.0 as f64, delta.1 as f64), size),
+            LayerInput::Scroll { delta, position } => self.zoom_at(
+                (position.0 as f64, position.1 as f64),
+                0.9f64.powf(delta as f64),
+                size,
+            ),
+            LayerInput::Click { button: MouseButton::Right, .. } => self.inspector.pin_hovered(),
+            LayerInput::Click { .. } => return false,
+        }
+        true
+    }
+}
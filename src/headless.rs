@@ -0,0 +1,179 @@
+use crate::camera::Camera;
+use crate::layer::{FrameContext, LayerStack, RenderTarget};
+use crate::pipeline_cache::PipelineCache;
+use egui_wgpu::wgpu;
+use winit::dpi::PhysicalSize;
+
+// GPU device without a window or surface, for rendering layers straight to images
+pub struct Headless {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub info: wgpu::AdapterInfo,
+    // shared with the app, so renders reuse pipelines it already compiled
+    pub pipeline_cache: PipelineCache,
+}
+
+impl Headless {
+    // Prefers a hardware adapter and falls back to a software one (llvmpipe, WARP)
+    // when none is available or `force_fallback` is set
+    pub async fn new(force_fallback: bool) -> Result<Self, String> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        let mut adapter = None;
+        if !force_fallback {
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::HighPerformance,
+                    force_fallback_adapter: false,
+                    compatible_surface: None,
+                })
+                .await;
+        }
+        if adapter.is_none() {
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    force_fallback_adapter: true,
+                    compatible_surface: None,
+                })
+                .await;
+        }
+        let adapter = adapter.ok_or("no GPU adapter available, not even a software fallback")?;
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("Headless Device"),
+                    required_features: adapter.features() & wgpu::Features::PIPELINE_CACHE,
+                    // software adapters often miss the WebGPU defaults
+                    required_limits: adapter.limits(),
+                    memory_hints: Default::default(),
+                },
+                None,
+            )
+            .await
+            .map_err(|err| format!("failed to create device: {err}"))?;
+
+        let info = adapter.get_info();
+        let pipeline_cache = PipelineCache::load(&device, &info);
+        Ok(Self {
+            device,
+            queue,
+            info,
+            pipeline_cache,
+        })
+    }
+
+    pub fn create_target(&self, format: wgpu::TextureFormat, size: PhysicalSize<u32>) -> Result<wgpu::Texture, String> {
+        let max = self.device.limits().max_texture_dimension_2d;
+        if size.width > max || size.height > max {
+            return Err(format!("{}x{} exceeds the adapter's texture limit of {max}", size.width, size.height));
+        }
+        Ok(self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Headless Target"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        }))
+    }
+
+    // Runs the enabled layers `frames` times into `target` (progressive layers
+    // accumulate across frames) and reads the result back
+    pub fn render(&self, layers: &mut LayerStack, target: &wgpu::Texture, frames: u32) -> Result<Vec<u8>, String> {
+        let size = PhysicalSize::new(target.width(), target.height());
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let camera = Camera::orbit(glam::Vec3::ZERO, 3.0);
+        layers.resize(&self.device, size);
+
+        for i in 0..frames.max(1) {
+            let frame = FrameContext {
+                device: &self.device,
+                queue: &self.queue,
+                size,
+                camera: &camera,
+                time: i as f32 / 60.0,
+                cursor: None,
+            };
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Headless Encoder") });
+            layers.render(&frame, &mut encoder, &RenderTarget::single(&view), None);
+            self.queue.submit(Some(encoder.finish()));
+        }
+
+        read_texture_rgba(&self.device, &self.queue, target)
+    }
+}
+
+// Copies a 4-byte-per-pixel texture to the CPU as tightly packed RGBA8;
+// fails if the readback buffer would exceed the device's `max_buffer_size`
+pub fn read_texture_rgba(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> Result<Vec<u8>, String> {
+    assert!(
+        matches!(
+            texture.format().remove_srgb_suffix(),
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Bgra8Unorm
+        ),
+        "cannot read back {:?} as RGBA8",
+        texture.format()
+    );
+    let (width, height) = (texture.width(), texture.height());
+    // rows in the copy must be aligned to 256 bytes
+    let unpadded = width * 4;
+    let padded = unpadded.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let size = u64::from(padded) * u64::from(height);
+    let max = device.limits().max_buffer_size;
+    if size > max {
+        return Err(format!("{width}x{height} needs a {size} byte readback buffer, this device allows {max}"));
+    }
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Readback Encoder") });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |result| result.expect("Failed to map readback buffer"));
+    device.poll(wgpu::Maintain::Wait);
+
+    let bgra = matches!(
+        texture.format(),
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+    );
+    let mut pixels = Vec::with_capacity(unpadded as usize * height as usize);
+    for row in slice.get_mapped_range().chunks_exact(padded as usize) {
+        pixels.extend_from_slice(&row[..unpadded as usize]);
+    }
+    if bgra {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    Ok(pixels)
+}
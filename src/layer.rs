@@ -0,0 +1,263 @@
+use crate::camera::Camera;
+use crate::profiler::Profiler;
+use egui_wgpu::wgpu;
+use std::any::Any;
+use winit::dpi::PhysicalSize;
+use winit::event::MouseButton;
+
+// Everything a layer may need while recording a frame
+pub struct FrameContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub size: PhysicalSize<u32>,
+    pub camera: &'a Camera,
+    // seconds since startup
+    pub time: f32,
+    // physical pixels in the viewport, None when the cursor is elsewhere
+    pub cursor: Option<(f32, f32)>,
+}
+
+// Format of `RenderTarget::depth`
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+// Where layers draw. With MSAA `view` is multisampled and every pass resolves
+// into `resolve_target`, so passes must store their samples for the next layer
+// and pipelines must match the count given to `Layer::set_sample_count`.
+pub struct RenderTarget<'a> {
+    pub view: &'a wgpu::TextureView,
+    pub resolve_target: Option<&'a wgpu::TextureView>,
+    // shared by all layers and cleared once per frame; present while an enabled
+    // layer `uses_depth`
+    pub depth: Option<&'a wgpu::TextureView>,
+}
+
+impl<'a> RenderTarget<'a> {
+    pub fn single(view: &'a wgpu::TextureView) -> Self {
+        Self {
+            view,
+            resolve_target: None,
+            depth: None,
+        }
+    }
+
+    pub fn color_attachment(&self, load: wgpu::LoadOp<wgpu::Color>) -> wgpu::RenderPassColorAttachment<'a> {
+        wgpu::RenderPassColorAttachment {
+            view: self.view,
+            resolve_target: self.resolve_target,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+
+    // Depth test against what earlier layers drew this frame
+    pub fn depth_attachment(&self) -> Option<wgpu::RenderPassDepthStencilAttachment<'a>> {
+        self.depth.map(|view| wgpu::RenderPassDepthStencilAttachment {
+            view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        })
+    }
+}
+
+// Pointer input aimed at the scene viewport, in physical pixels
+pub enum LayerInput {
+    Drag { delta: (f32, f32) },
+    Scroll { delta: f32, position: (f32, f32) },
+    Click { button: MouseButton },
+}
+
+// A scene renderer in the `LayerStack`. Layers draw on top of whatever the
+// previous layers left in the target, so they should load rather than clear it.
+pub trait Layer: Any {
+    fn name(&self) -> &str;
+
+    // Take over the user's parameters from the previous instance of this layer
+    // when the device is re-created; `old` is the layer it replaces
+    fn restore(&mut self, _old: Box<dyn Any>) {}
+
+    fn resize(&mut self, _device: &wgpu::Device, _size: PhysicalSize<u32>) {}
+
+    // Rebuild render pipelines for a new MSAA sample count of the target
+    fn set_sample_count(&mut self, _device: &wgpu::Device, _sample_count: u32) {}
+
+    // Whether the layer draws depth-tested geometry into `RenderTarget::depth`
+    fn uses_depth(&self) -> bool {
+        false
+    }
+
+    // Whether the image changes from frame to frame without input, e.g. with
+    // `frame.time`; the app redraws continuously while an enabled layer does
+    fn animating(&self) -> bool {
+        false
+    }
+
+    // Upload uniforms and other per-frame data before any pass is recorded
+    fn prepare(&mut self, _frame: &FrameContext) {}
+
+    fn render(&mut self, frame: &FrameContext, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget);
+
+    // Controls shown under the layer's entry in the layer list
+    fn ui(&mut self, _ui: &mut egui::Ui) {}
+
+    // egui painting on top of the scene. The painter is clipped to the scene image,
+    // which spans pixels (0, 0) to `frame.size`.
+    fn overlay(&mut self, _painter: &egui::Painter, _frame: &FrameContext) {}
+
+    // Current parameters as text, stored in screenshot metadata
+    fn metadata(&self) -> Option<String> {
+        None
+    }
+
+    // Return true to stop the event from reaching lower layers and the camera
+    fn input(&mut self, _event: &LayerInput, _size: PhysicalSize<u32>) -> bool {
+        false
+    }
+}
+
+struct LayerEntry {
+    layer: Box<dyn Layer>,
+    enabled: bool,
+}
+
+// Ordered scene layers, drawn first to last before egui
+#[derive(Default)]
+pub struct LayerStack {
+    entries: Vec<LayerEntry>,
+}
+
+impl LayerStack {
+    pub fn push(&mut self, layer: impl Layer + 'static, enabled: bool) {
+        self.entries.push(LayerEntry {
+            layer: Box::new(layer),
+            enabled,
+        });
+    }
+
+    // Carries enabled flags, order and parameters over from the stack on a
+    // previous device, matching layers by name
+    pub fn restore(&mut self, old: LayerStack) {
+        let mut entries = Vec::with_capacity(self.entries.len());
+        for old_entry in old.entries {
+            let Some(i) = self.entries.iter().position(|entry| entry.layer.name() == old_entry.layer.name()) else {
+                continue;
+            };
+            let mut entry = self.entries.remove(i);
+            entry.enabled = old_entry.enabled;
+            entry.layer.restore(old_entry.layer);
+            entries.push(entry);
+        }
+        entries.append(&mut self.entries);
+        self.entries = entries;
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: PhysicalSize<u32>) {
+        for entry in &mut self.entries {
+            entry.layer.resize(device, size);
+        }
+    }
+
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        for entry in &mut self.entries {
+            entry.layer.set_sample_count(device, sample_count);
+        }
+    }
+
+    pub fn uses_depth(&self) -> bool {
+        self.entries.iter().any(|entry| entry.enabled && entry.layer.uses_depth())
+    }
+
+    // With a profiler, each layer's passes are timed on the GPU
+    pub fn render(
+        &mut self,
+        frame: &FrameContext,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &RenderTarget,
+        mut profiler: Option<&mut Profiler>,
+    ) {
+        if let Some(depth) = target.depth {
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Depth Clear"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+        }
+        for entry in self.entries.iter_mut().filter(|entry| entry.enabled) {
+            entry.layer.prepare(frame);
+        }
+        for entry in self.entries.iter_mut().filter(|entry| entry.enabled) {
+            if let Some(profiler) = profiler.as_deref_mut() {
+                profiler.begin_gpu(encoder, entry.layer.name());
+            }
+            entry.layer.render(frame, encoder, target);
+            if let Some(profiler) = profiler.as_deref_mut() {
+                profiler.end_gpu(encoder);
+            }
+        }
+    }
+
+    pub fn animating(&self) -> bool {
+        self.entries.iter().any(|entry| entry.enabled && entry.layer.animating())
+    }
+
+    pub fn overlay(&mut self, painter: &egui::Painter, frame: &FrameContext) {
+        for entry in self.entries.iter_mut().filter(|entry| entry.enabled) {
+            entry.layer.overlay(painter, frame);
+        }
+    }
+
+    // (layer name, parameters) of the enabled layers
+    pub fn metadata(&self) -> Vec<(String, String)> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.enabled)
+            .filter_map(|entry| Some((entry.layer.name().to_string(), entry.layer.metadata()?)))
+            .collect()
+    }
+
+    // Topmost layers get the first chance to capture input
+    pub fn input(&mut self, event: &LayerInput, size: PhysicalSize<u32>) -> bool {
+        self.entries
+            .iter_mut()
+            .rev()
+            .filter(|entry| entry.enabled)
+            .any(|entry| entry.layer.input(event, size))
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let count = self.entries.len();
+        let mut swap = None;
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            ui.push_id(i, |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut entry.enabled, entry.layer.name());
+                    if ui.add_enabled(i > 0, egui::Button::new("⬆").small()).clicked() {
+                        swap = Some((i - 1, i));
+                    }
+                    if ui.add_enabled(i + 1 < count, egui::Button::new("⬇").small()).clicked() {
+                        swap = Some((i, i + 1));
+                    }
+                });
+                if entry.enabled {
+                    ui.indent("layer_ui", |ui| entry.layer.ui(ui));
+                }
+            });
+        }
+        if let Some((a, b)) = swap {
+            self.entries.swap(a, b);
+        }
+    }
+}
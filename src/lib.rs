@@ -0,0 +1,2 @@
+// Parts of the app usable on their own, without a window or GPU
+pub mod complex;
@@ -1,23 +1,77 @@
+mod adapter;
 mod app;
 mod egui_tools;
+mod fractal_renderer;
+mod headless;
 mod app_renderer;
+mod buddhabrot;
+mod camera;
+mod cli;
+mod cube_layer;
+mod layer;
 mod mandelbrot;
+mod orbit_overlay;
+mod pipeline_cache;
+mod post;
+mod profiler;
+mod raymarch;
+mod recorder;
+mod render_scale;
+mod screen_layer;
+mod screenshot;
+mod settings;
+mod shape_renderer;
+mod tonemap;
+mod uniform;
+mod viewport;
 
 use winit::event_loop::{ControlFlow, EventLoop};
+use winit_egui::complex;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("render-fractal") {
+        let render_args = match cli::RenderArgs::parse(&args[1..]) {
+            Ok(render_args) => render_args,
+            Err(err) => {
+                eprintln!("error: {err}");
+                eprintln!("{}", cli::USAGE);
+                std::process::exit(2);
+            }
+        };
+        if let Err(err) = cli::render_fractal(&render_args) {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let app_args = match cli::AppArgs::parse(&args) {
+        Ok(app_args) => app_args,
+        Err(err) => {
+            eprintln!("error: {err}");
+            eprintln!("{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if app_args.list_adapters {
+        cli::list_adapters();
+        return;
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     {
-        pollster::block_on(run());
+        pollster::block_on(run(app_args.adapter_override()));
     }
 }
 
-async fn run() {
+async fn run(adapter_override: Option<adapter::AdapterChoice>) {
     let event_loop = EventLoop::new().unwrap();
 
-    event_loop.set_control_flow(ControlFlow::Poll);
+    // the app requests redraws itself, see `App::about_to_wait`
+    event_loop.set_control_flow(ControlFlow::Wait);
 
-    let mut app = app::App::new();
+    let mut app = app::App::new(adapter_override);
 
     event_loop.run_app(&mut app).expect("Failed to run app");
 }
\ No newline at end of file
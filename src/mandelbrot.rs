@@ -1,114 +1,385 @@
-// fn draw_png (image_width: u32, image_height: u32, ) {
-//     let ratio = image_height as f32 / image_width as f32;
-//     let center = Point {x: 0.0, y: 0.0};
-//     let width = 4.0 as f32;
-//     let height = width * ratio;
-//     let init_x = center.x - (width / 2 as f32);
-//     let init_y = center.y - (height / 2 as f32);
-//     let inc = width / (image_width as f32);
-
-//     let mut image_buffer = image::ImageBuffer::new(
-//         image_width, image_height);
-
-//     for (x, y, pixel) in image_buffer.enumerate_pixels_mut() {
-//         // let u = x as f32 / image_height as f32;
-//         // let v = y as f32 / image_height as f32;
-//         // let t = mandelbrot(2.5 * (u - 0.5) - 1.4, 2.5 * (v - 0.5));
-//         let u = init_x + (x as f32 * inc);
-//         let v = init_y + (y as f32 * inc);
-//         let t = mandelbrot(u, v);
-//         *pixel = image::Rgb(color((2.0 * t + 0.5) % 1.0));
-//     }
-
-//     image_buffer.save("mandelbrot.png").unwrap();
-// }
-
-struct FractalPlot {
-    center: Point,
-    width: f32,
-    height: f32,
-    init_x: f32,
-    init_y: f32,
-    inc: f32,
+use crate::complex::Complex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use winit::dpi::PhysicalSize;
+
+const TILE_SIZE: u32 = 64;
+
+pub const ESCAPE_RADIUS_SQ: f64 = 32.0;
+
+// Maps screen pixels to points in the complex plane; y grows downwards on
+// screen and upwards on the imaginary axis.
+pub struct FractalPlot {
+    init_x: f64,
+    init_y: f64,
+    inc: f64,
 }
 
 impl FractalPlot {
-    pub fn new(center: Point, screen_size: winit::dpi::PhysicalSize<u32>) -> Self {
-        let ratio = screen_size.height as f32 / screen_size.width as f32;
-        let width = 4.0 as f32;
+    pub fn new(center: Complex, width: f64, screen_size: PhysicalSize<u32>) -> Self {
+        let ratio = screen_size.height as f64 / screen_size.width as f64;
         let height = width * ratio;
-        let init_x = center.x - (width / 2 as f32);
-        let init_y = center.y - (height / 2 as f32);
-        let inc = width / (screen_size.width as f32);
+        let init_x = center.re - (width / 2.0);
+        let init_y = center.im + (height / 2.0);
+        let inc = width / (screen_size.width as f64);
+
+        Self { init_x, init_y, inc }
+    }
 
-        Self { center, width, height, init_x, init_y, inc }
+    pub fn get_point(&self, screen_coordinate: (f64, f64)) -> Complex {
+        let u = self.init_x + (screen_coordinate.0 * self.inc);
+        let v = self.init_y - (screen_coordinate.1 * self.inc);
+        Complex::new(u, v)
     }
 
-    pub fn getPoint(&self, screenCoordinate: (i16, i16)) -> (f32, f32) {
-        let u = self.init_x + (screenCoordinate.0 as f32 * self.inc);
-        let v = self.init_y + (screenCoordinate.1 as f32 * self.inc);
-        return (u, v)
+    pub fn get_pixel(&self, point: Complex) -> (f64, f64) {
+        ((point.re - self.init_x) / self.inc, (self.init_y - point.im) / self.inc)
     }
 }
 
-struct Point {
-    pub x: f32,
-    pub y: f32
+#[derive(Clone, Copy, Debug)]
+pub struct FractalView {
+    pub center: Complex,
+    pub width: f64,
+    pub max_iter: u32,
 }
 
-#[derive(Clone, Copy)]
-struct Complex {
-    pub a: f32,
-    pub b: f32,
+impl Default for FractalView {
+    fn default() -> Self {
+        Self {
+            center: Complex::new(-0.5, 0.0),
+            width: 4.0,
+            max_iter: 256,
+        }
+    }
 }
 
-impl std::ops::Add for Complex {
-    type Output = Self;
+// Smooth (fractional) iteration count, or None for points that never escape
+pub fn mandelbrot(c: Complex, max: u32) -> Option<f64> {
+    if in_main_cardioid(c) || in_period2_bulb(c) {
+        return None;
+    }
 
-    fn add(self, rhs: Self) -> Self::Output {
-        Complex {
-            a: self.a + rhs.a,
-            b: self.b + rhs.b,
+    let mut z = Complex::zero();
+    // Brent-style cycle detection: compare against a snapshot taken at doubling intervals
+    let mut saved = z;
+    // derivative of the orbit since the snapshot; only an attracting cycle (|dz| < 1)
+    // proves the point is inside, orbits can pass close to repelling ones and escape
+    let mut dz = Complex::one();
+    let mut period = 8;
+    let mut i = 0;
+    while i < max && z.norm_sqr() < ESCAPE_RADIUS_SQ {
+        dz = (z * dz).scale(2.0);
+        z = z * z + c;
+        i += 1;
+
+        if (z - saved).norm_sqr() < PERIOD_EPSILON && dz.norm_sqr() < 1.0 {
+            return None;
+        }
+        if i == period {
+            saved = z;
+            dz = Complex::one();
+            period *= 2;
         }
     }
+    if i == max {
+        return None;
+    }
+    Some(i as f64 - z.norm_sqr().log2().log2())
 }
 
-impl std::ops::Mul for Complex {
-    type Output = Self;
+// Points visited by z -> z^2 + c, ending with the first point outside the escape radius
+pub fn orbit(c: Complex, max: u32) -> Vec<Complex> {
+    let mut z = Complex::zero();
+    let mut points = Vec::new();
+    while points.len() < max as usize && z.norm_sqr() < ESCAPE_RADIUS_SQ {
+        z = z * z + c;
+        points.push(z);
+    }
+    points
+}
 
-    fn mul(self, rhs: Self) -> Self::Output {
-        Complex { 
-            a: self.a * rhs.a - self.b * rhs.b, 
-            b: self.a * rhs.b + self.b * rhs.a,
+// Orbits closer than this to an earlier point are treated as periodic
+const PERIOD_EPSILON: f64 = 1e-24;
+
+pub fn in_main_cardioid(c: Complex) -> bool {
+    let x = c.re - 0.25;
+    let q = x * x + c.im * c.im;
+    q * (q + x) <= 0.25 * c.im * c.im
+}
+
+pub fn in_period2_bulb(c: Complex) -> bool {
+    let x = c.re + 1.0;
+    x * x + c.im * c.im <= 1.0 / 16.0
+}
+
+pub enum Palette {
+    Cosine,
+    // Evenly spaced color stops, sampled cyclically
+    Gradient(Vec<[f64; 3]>),
+}
+
+impl Palette {
+    // One `rrggbb` / `#rrggbb` color per line; blank lines and `//` comments are skipped
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read palette {path}: {err}"))?;
+        let mut stops = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split("//").next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let hex = line.trim_start_matches('#');
+            let value = u32::from_str_radix(hex, 16)
+                .ok()
+                .filter(|_| hex.len() == 6)
+                .ok_or_else(|| format!("{path}:{}: expected a hex color, got `{line}`", n + 1))?;
+            stops.push([
+                ((value >> 16) & 0xff) as f64 / 255.0,
+                ((value >> 8) & 0xff) as f64 / 255.0,
+                (value & 0xff) as f64 / 255.0,
+            ]);
         }
+        if stops.is_empty() {
+            return Err(format!("palette {path} has no colors"));
+        }
+        Ok(Palette::Gradient(stops))
     }
-}
 
-impl Complex {
-    fn arg_sq(self) -> f32 {
-        self.a * self.a + self.b * self.b
+    pub fn sample(&self, t: f64) -> [u8; 3] {
+        match self {
+            Palette::Cosine => color(t),
+            Palette::Gradient(stops) => {
+                let x = t.rem_euclid(1.0) * stops.len() as f64;
+                let i = x.floor() as usize % stops.len();
+                let j = (i + 1) % stops.len();
+                let f = x.fract();
+                let mix = |k: usize| (stops[i][k] + (stops[j][k] - stops[i][k]) * f) * 255.0;
+                [mix(0) as u8, mix(1) as u8, mix(2) as u8]
+            }
+        }
     }
 }
 
-fn mandelbrot(x: f32, y: f32) -> f32 {
-    let mut z = Complex { a: 0.0, b: 0.0 };
-    let c = Complex { a: x, b: y };
-    let max = 256;
-    let mut i = 0;
-    while i < max && z.arg_sq() < 32.0 {
-        z = z * z + c;
-        i += 1;
+// Renders the view into a tightly packed RGBA8 buffer, splitting the image into
+// tiles that are picked up by one worker thread per core. With `subdivide` set,
+// tiles are filled with Mariani-Silver subdivision instead of per-pixel iteration,
+// which misses escaping filaments thinner than a pixel that cross a rectangle's border
+// between samples.
+pub fn render_rgba(
+    view: &FractalView,
+    size: PhysicalSize<u32>,
+    palette: &Palette,
+    subdivide: bool,
+) -> Vec<u8> {
+    let plot = FractalPlot::new(view.center, view.width, size);
+    let tiles_x = size.width.div_ceil(TILE_SIZE);
+    let tiles_y = size.height.div_ceil(TILE_SIZE);
+    let tile_count = tiles_x as usize * tiles_y as usize;
+
+    let pixels = Mutex::new(vec![0u8; size.width as usize * size.height as usize * 4]);
+    let next_tile = AtomicUsize::new(0);
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let log_max = (view.max_iter as f64 + 1.0).ln();
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let tile = next_tile.fetch_add(1, Ordering::Relaxed);
+                if tile >= tile_count {
+                    break;
+                }
+                let x0 = (tile as u32 % tiles_x) * TILE_SIZE;
+                let y0 = (tile as u32 / tiles_x) * TILE_SIZE;
+                let x1 = (x0 + TILE_SIZE).min(size.width);
+                let y1 = (y0 + TILE_SIZE).min(size.height);
+
+                let mut tile = Tile::new(&plot, view.max_iter, x0, y0, x1 - x0, y1 - y0);
+                if subdivide {
+                    tile.subdivide(0, 0, tile.width - 1, tile.height - 1);
+                }
+
+                let mut local = Vec::with_capacity((tile.width * tile.height * 4) as usize);
+                for y in 0..tile.height {
+                    for x in 0..tile.width {
+                        let rgb = match tile.sample(x, y) {
+                            Some(n) => palette.sample((n.max(0.0) + 1.0).ln() / log_max),
+                            None => [0, 0, 0],
+                        };
+                        local.extend_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+                    }
+                }
+
+                let row_len = ((x1 - x0) * 4) as usize;
+                let mut pixels = pixels.lock().unwrap();
+                for (row, y) in (y0..y1).enumerate() {
+                    let start = ((y * size.width + x0) * 4) as usize;
+                    pixels[start..start + row_len]
+                        .copy_from_slice(&local[row * row_len..(row + 1) * row_len]);
+                }
+            });
+        }
+    });
+
+    pixels.into_inner().unwrap()
+}
+
+// Rectangles smaller than this are iterated pixel by pixel
+const MIN_SUBDIVIDE: u32 = 4;
+
+// Lazily evaluated iteration results for one tile
+struct Tile<'a> {
+    plot: &'a FractalPlot,
+    max_iter: u32,
+    x0: u32,
+    y0: u32,
+    width: u32,
+    height: u32,
+    samples: Vec<Option<Option<f64>>>,
+}
+
+impl<'a> Tile<'a> {
+    fn new(plot: &'a FractalPlot, max_iter: u32, x0: u32, y0: u32, width: u32, height: u32) -> Self {
+        let samples = vec![None; (width * height) as usize];
+        Self { plot, max_iter, x0, y0, width, height, samples }
+    }
+
+    fn sample(&mut self, x: u32, y: u32) -> Option<f64> {
+        let index = (y * self.width + x) as usize;
+        *self.samples[index].get_or_insert_with(|| {
+            let px = (self.x0 + x) as f64 + 0.5;
+            let py = (self.y0 + y) as f64 + 0.5;
+            mandelbrot(self.plot.get_point((px, py)), self.max_iter)
+        })
+    }
+
+    // Mariani-Silver: the set is connected, so a rectangle whose border lies
+    // entirely inside it is inside too. Bounds are inclusive.
+    fn subdivide(&mut self, x0: u32, y0: u32, x1: u32, y1: u32) {
+        let mut border_inside = true;
+        for x in x0..=x1 {
+            border_inside &= self.sample(x, y0).is_none();
+            border_inside &= self.sample(x, y1).is_none();
+        }
+        for y in y0..=y1 {
+            border_inside &= self.sample(x0, y).is_none();
+            border_inside &= self.sample(x1, y).is_none();
+        }
+
+        if border_inside {
+            for y in y0 + 1..y1 {
+                for x in x0 + 1..x1 {
+                    self.samples[(y * self.width + x) as usize] = Some(None);
+                }
+            }
+            return;
+        }
+
+        if x1 - x0 < MIN_SUBDIVIDE || y1 - y0 < MIN_SUBDIVIDE {
+            return;
+        }
+
+        let mx = (x0 + x1) / 2;
+        let my = (y0 + y1) / 2;
+        self.subdivide(x0, y0, mx, my);
+        self.subdivide(mx, y0, x1, my);
+        self.subdivide(x0, my, mx, y1);
+        self.subdivide(mx, my, x1, y1);
     }
-    return (i as f32 - z.arg_sq().log2().log2()) / (max as f32);
 }
 
-fn color(t: f32) -> [u8; 3] {
+fn color(t: f64) -> [u8; 3] {
     let a = (0.5, 0.5, 0.5);
     let b = (0.5, 0.5, 0.5);
     let c = (1.0, 1.0, 1.0);
     let d = (0.0, 0.10, 0.20);
-    let r = b.0 * (6.28318 * (c.0 * t + d.0)).cos() + a.0;
-    let g = b.1 * (6.28318 * (c.1 * t + d.1)).cos() + a.1;
-    let b = b.2 * (6.28318 * (c.2 * t + d.2)).cos() + a.2;
+    let tau = std::f64::consts::TAU;
+    let r = b.0 * (tau * (c.0 * t + d.0)).cos() + a.0;
+    let g = b.1 * (tau * (c.1 * t + d.1)).cos() + a.1;
+    let b = b.2 * (tau * (c.2 * t + d.2)).cos() + a.2;
     [(255.0 * r) as u8, (255.0 * g) as u8, (255.0 * b) as u8]
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Plain escape-time iteration without the cardioid, bulb or periodicity shortcuts
+    fn escape_time(c: Complex, max: u32) -> Option<f64> {
+        let mut z = Complex::zero();
+        let mut i = 0;
+        while i < max && z.norm_sqr() < ESCAPE_RADIUS_SQ {
+            z = z * z + c;
+            i += 1;
+        }
+        (i < max).then(|| i as f64 - z.norm_sqr().log2().log2())
+    }
+
+    fn check(c: Complex, max: u32) {
+        assert_eq!(mandelbrot(c, max), escape_time(c, max), "c = {c}, max = {max}");
+    }
+
+    #[test]
+    fn matches_escape_time_on_grid() {
+        for y in 0..=120 {
+            for x in 0..=160 {
+                let c = Complex::new(-2.2 + x as f64 * 0.02, -1.2 + y as f64 * 0.02);
+                check(c, 500);
+            }
+        }
+    }
+
+    #[test]
+    fn matches_escape_time_near_cardioid_and_bulb() {
+        for k in 0..360 {
+            let t = (k as f64).to_radians();
+            let rotation = Complex::from_polar(1.0, t);
+            // main cardioid boundary: c = e^(it) / 2 - e^(2it) / 4
+            let cardioid = rotation.scale(0.5) - (rotation * rotation).scale(0.25);
+            // period 2 bulb boundary: |c + 1| = 1/4
+            let bulb = Complex::new(-1.0, 0.0) + rotation.scale(0.25);
+            for scale in [0.99, 0.999, 0.99999, 1.00001, 1.001, 1.01] {
+                check(cardioid.scale(scale), 2000);
+                check(Complex::new(-1.0, 0.0) + (bulb - Complex::new(-1.0, 0.0)).scale(scale), 2000);
+            }
+        }
+    }
+
+    #[test]
+    fn matches_escape_time_at_deep_boundary_points() {
+        // neck between the cardioid and the period 2 bulb, and outside the cusp;
+        // these escape after roughly pi / eps iterations
+        for eps in [1e-2, 1e-3, 1e-4] {
+            check(Complex::new(-0.75, eps), 100_000);
+            check(Complex::new(0.25 + eps * eps, 0.0), 100_000);
+        }
+        check(Complex::new(0.25, 0.0), 10_000);
+        check(Complex::new(-0.75, 0.0), 10_000);
+        check(Complex::new(-1.25, 0.0), 10_000);
+        // seahorse valley, elephant valley and a minibrot antenna
+        check(Complex::new(-0.743_643_887_037_151, 0.131_825_904_205_33), 20_000);
+        check(Complex::new(0.285_001, 0.01), 20_000);
+        check(Complex::new(-1.768_778_833, -0.001_738_996), 20_000);
+        check(Complex::new(-0.1011, 0.9563), 20_000);
+    }
+
+    #[test]
+    fn subdivision_matches_per_pixel() {
+        let views = [
+            FractalView::default(),
+            FractalView { center: Complex::new(-1.0, 0.0), width: 0.8, max_iter: 500 },
+            FractalView { center: Complex::new(-1.768_778_833, -0.001_738_996), width: 1e-4, max_iter: 1000 },
+            FractalView { center: Complex::new(0.3, 0.0), width: 0.2, max_iter: 300 },
+        ];
+
+        let palette = Palette::Cosine;
+        for view in views {
+            let size = PhysicalSize::new(150, 110);
+            let plain = render_rgba(&view, size, &palette, false);
+            let subdivided = render_rgba(&view, size, &palette, true);
+            let mismatch = plain.chunks(4).zip(subdivided.chunks(4)).position(|(a, b)| a != b);
+            assert_eq!(mismatch, None, "first differing pixel index for {view:?}");
+        }
+    }
+}
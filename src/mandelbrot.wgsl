@@ -0,0 +1,54 @@
+struct Uniforms {
+    // complex coordinate of the top-left pixel corner
+    origin: vec2<f32>,
+    // complex-plane units per pixel
+    inc: f32,
+    max_iter: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+// Renders a full-screen triangle without vertex data
+@vertex
+fn vs_main(@builtin(vertex_index) vert_index: u32) -> @builtin(position) vec4<f32> {
+    let pos = array(
+        vec2(-1.0, -1.0),
+        vec2(3.0, -1.0),
+        vec2(-1.0, 3.0),
+    );
+    return vec4(pos[vert_index], 0.0, 1.0);
+}
+
+// Same cosine palette as `mandelbrot::color`
+fn palette(t: f32) -> vec3<f32> {
+    let d = vec3(0.0, 0.10, 0.20);
+    return 0.5 * cos(6.28318530718 * (t + d)) + 0.5;
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let c = uniforms.origin + vec2(frag_position.x, -frag_position.y) * uniforms.inc;
+
+    // main cardioid and period-2 bulb
+    let x = c.x - 0.25;
+    let q = x * x + c.y * c.y;
+    let bulb = (c.x + 1.0) * (c.x + 1.0) + c.y * c.y;
+    if q * (q + x) <= 0.25 * c.y * c.y || bulb <= 0.0625 {
+        return vec4(0.0, 0.0, 0.0, 1.0);
+    }
+
+    var z = vec2(0.0, 0.0);
+    var i = 0u;
+    while i < uniforms.max_iter && dot(z, z) < 32.0 {
+        z = vec2(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+        i++;
+    }
+    if i == uniforms.max_iter {
+        return vec4(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let n = f32(i) - log2(log2(dot(z, z)));
+    let t = log(max(n, 0.0) + 1.0) / log(f32(uniforms.max_iter) + 1.0);
+    return vec4(palette(t), 1.0);
+}
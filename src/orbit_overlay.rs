@@ -0,0 +1,135 @@
+use crate::complex::Complex;
+use crate::mandelbrot::{self, FractalPlot, ESCAPE_RADIUS_SQ};
+use winit::dpi::PhysicalSize;
+
+// Orbits can be as long as max_iter; past this they only add clutter
+const MAX_DRAWN_POINTS: usize = 1000;
+
+const PIN_COLORS: [egui::Color32; 6] = [
+    egui::Color32::from_rgb(255, 99, 71),
+    egui::Color32::from_rgb(124, 252, 0),
+    egui::Color32::from_rgb(30, 144, 255),
+    egui::Color32::from_rgb(255, 215, 0),
+    egui::Color32::from_rgb(238, 130, 238),
+    egui::Color32::from_rgb(64, 224, 208),
+];
+
+struct OrbitInfo {
+    points: Vec<Complex>,
+    escaped: bool,
+    // |z| of the last iterate
+    radius: f64,
+}
+
+impl OrbitInfo {
+    fn new(c: Complex, max_iter: u32) -> Self {
+        let points = mandelbrot::orbit(c, max_iter);
+        let last = points.last().copied().unwrap_or_default();
+        Self {
+            escaped: last.norm_sqr() >= ESCAPE_RADIUS_SQ,
+            radius: last.abs(),
+            points,
+        }
+    }
+
+    fn summary(&self) -> String {
+        if self.escaped {
+            format!("escaped after {} iterations, |z| = {:.3}", self.points.len(), self.radius)
+        } else {
+            format!("bounded for {} iterations, |z| = {:.3}", self.points.len(), self.radius)
+        }
+    }
+}
+
+// Hover readout and orbit polylines for the 2D fractal view
+pub struct OrbitInspector {
+    pub show_orbit: bool,
+    hovered: Option<Complex>,
+    pinned: Vec<Complex>,
+}
+
+impl OrbitInspector {
+    pub fn new() -> Self {
+        Self {
+            show_orbit: true,
+            hovered: None,
+            pinned: Vec::new(),
+        }
+    }
+
+    pub fn set_hovered(&mut self, point: Option<Complex>) {
+        self.hovered = point;
+    }
+
+    pub fn pin_hovered(&mut self) {
+        if let Some(point) = self.hovered {
+            self.pinned.push(point);
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, max_iter: u32) {
+        ui.checkbox(&mut self.show_orbit, "Show orbits");
+        match self.hovered {
+            Some(c) => {
+                ui.label(format!("c = {c:.12}"));
+                ui.label(OrbitInfo::new(c, max_iter).summary());
+            }
+            None => {
+                ui.label("Hover the fractal to inspect a point");
+            }
+        }
+
+        ui.label("Right click to pin a point");
+        let mut remove = None;
+        for (i, c) in self.pinned.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.colored_label(PIN_COLORS[i % PIN_COLORS.len()], format!("{c:.8}"));
+                if ui.small_button("x").clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove {
+            self.pinned.remove(i);
+        }
+        if !self.pinned.is_empty() && ui.button("Clear pinned").clicked() {
+            self.pinned.clear();
+        }
+    }
+
+    // `plot` maps the pixels of a `size` image stretched over the painter's clip rect
+    pub fn paint(&self, painter: &egui::Painter, plot: &FractalPlot, size: PhysicalSize<u32>, max_iter: u32) {
+        let rect = painter.clip_rect();
+        let scale = rect.size() / egui::vec2(size.width as f32, size.height as f32);
+        let to_screen = |z: Complex| {
+            let (x, y) = plot.get_pixel(z);
+            rect.min + egui::vec2(x as f32, y as f32) * scale
+        };
+
+        let draw = |c: Complex, color: egui::Color32| {
+            if !self.show_orbit {
+                return;
+            }
+            let info = OrbitInfo::new(c, max_iter);
+            let mut points = vec![to_screen(c)];
+            points.extend(info.points.iter().take(MAX_DRAWN_POINTS).map(|z| to_screen(*z)));
+            painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, color)));
+            painter.circle_filled(to_screen(c), 3.0, color);
+        };
+
+        for (i, c) in self.pinned.iter().enumerate() {
+            draw(*c, PIN_COLORS[i % PIN_COLORS.len()]);
+        }
+        if let Some(c) = self.hovered {
+            draw(c, egui::Color32::WHITE);
+
+            // Readout next to the cursor
+            let text = format!("{c:.10}\n{}", OrbitInfo::new(c, max_iter).summary());
+            let galley = painter.layout_no_wrap(text, egui::FontId::monospace(12.0), egui::Color32::WHITE);
+            let pos = to_screen(c) + egui::vec2(14.0, 14.0);
+            let rect = egui::Rect::from_min_size(pos, galley.size()).expand(4.0);
+            painter.rect_filled(rect, 3.0, egui::Color32::from_black_alpha(180));
+            painter.galley(pos, galley, egui::Color32::WHITE);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::settings;
+use egui_wgpu::wgpu;
+use std::path::PathBuf;
+
+const MAGIC: &[u8; 8] = b"WEPLCACH";
+// bump when the file layout changes; files with another version are ignored
+const FILE_VERSION: u32 = 1;
+// magic, version, checksum of the data
+const HEADER_LEN: usize = 8 + 4 + 8;
+
+// wgpu pipeline cache stored in a file per adapter in the user cache directory.
+// Only Vulkan has one; elsewhere `get` returns None and pipelines compile from scratch.
+pub struct PipelineCache {
+    cache: Option<wgpu::PipelineCache>,
+    path: Option<PathBuf>,
+    // checksum of the data in the file, so an unchanged cache is not rewritten
+    saved_checksum: Option<u64>,
+}
+
+impl PipelineCache {
+    // Starts empty when the file is missing, from another version or damaged
+    pub fn load(device: &wgpu::Device, adapter_info: &wgpu::AdapterInfo) -> Self {
+        let mut cache = Self {
+            cache: None,
+            path: None,
+            saved_checksum: None,
+        };
+        if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            return cache;
+        }
+        let (Some(dir), Some(key)) = (settings::cache_dir(), wgpu::util::pipeline_cache_key(adapter_info)) else {
+            return cache;
+        };
+        let path = dir.join(format!("{key}.bin"));
+        let file = std::fs::read(&path).unwrap_or_default();
+        let data = match decode(&file) {
+            Ok(data) => Some(data),
+            Err(err) => {
+                if !file.is_empty() {
+                    println!("Ignoring pipeline cache {}: {err}", path.display());
+                }
+                None
+            }
+        };
+        cache.cache = match data.and_then(|data| create(device, Some(data))) {
+            Some(loaded) => {
+                cache.saved_checksum = data.map(checksum);
+                Some(loaded)
+            }
+            None => create(device, None),
+        };
+        cache.path = Some(path);
+        cache
+    }
+
+    pub fn get(&self) -> Option<&wgpu::PipelineCache> {
+        self.cache.as_ref()
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        let (Some(cache), Some(path)) = (&self.cache, &self.path) else {
+            return Ok(());
+        };
+        let Some(data) = cache.get_data() else {
+            return Ok(());
+        };
+        let sum = checksum(&data);
+        if self.saved_checksum == Some(sum) {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| format!("failed to create {}: {err}", dir.display()))?;
+        }
+        let mut file = Vec::with_capacity(HEADER_LEN + data.len());
+        file.extend_from_slice(MAGIC);
+        file.extend_from_slice(&FILE_VERSION.to_le_bytes());
+        file.extend_from_slice(&sum.to_le_bytes());
+        file.extend_from_slice(&data);
+        // written beside the old file and renamed over it, so a crash never leaves half a cache
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, file).map_err(|err| format!("failed to write {}: {err}", temp.display()))?;
+        std::fs::rename(&temp, path).map_err(|err| format!("failed to replace {}: {err}", path.display()))?;
+        self.saved_checksum = Some(sum);
+        Ok(())
+    }
+}
+
+// The data inside a cache file, if the header and checksum hold up
+fn decode(file: &[u8]) -> Result<&[u8], String> {
+    if file.len() < HEADER_LEN || &file[..8] != MAGIC {
+        return Err("not a pipeline cache file".to_string());
+    }
+    let version = u32::from_le_bytes(file[8..12].try_into().unwrap());
+    if version != FILE_VERSION {
+        return Err(format!("file version {version}, expected {FILE_VERSION}"));
+    }
+    let data = &file[HEADER_LEN..];
+    if checksum(data) != u64::from_le_bytes(file[12..20].try_into().unwrap()) {
+        return Err("checksum mismatch".to_string());
+    }
+    Ok(data)
+}
+
+// wgpu rejects data from another driver version, but reports it as a validation
+// error rather than falling back, so that is caught here too
+fn create(device: &wgpu::Device, data: Option<&[u8]>) -> Option<wgpu::PipelineCache> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    // SAFETY: the checksum shows `data` is unchanged since `get_data` returned it,
+    // and wgpu checks that it was made by this adapter and driver
+    let cache = unsafe {
+        device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+            label: Some("Pipeline Cache"),
+            data,
+            fallback: true,
+        })
+    };
+    match pollster::block_on(device.pop_error_scope()) {
+        None => Some(cache),
+        Some(err) => {
+            println!("Failed to create pipeline cache: {err}");
+            None
+        }
+    }
+}
+
+// FNV-1a
+fn checksum(data: &[u8]) -> u64 {
+    data.iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
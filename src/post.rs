@@ -0,0 +1,511 @@
+use crate::uniform::{self, impl_uniform, UniformBuffer};
+use egui_wgpu::wgpu;
+use std::collections::HashMap;
+use winit::dpi::PhysicalSize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PostEffectKind {
+    Bloom,
+    Fxaa,
+    ChromaticAberration,
+    Vignette,
+    FilmGrain,
+    Scanlines,
+    Pixelate,
+}
+
+// (label, default, range) of a slider
+type Param = (&'static str, f32, std::ops::RangeInclusive<f32>);
+
+impl PostEffectKind {
+    const ALL: [PostEffectKind; 7] = [
+        PostEffectKind::Bloom,
+        PostEffectKind::Fxaa,
+        PostEffectKind::ChromaticAberration,
+        PostEffectKind::Vignette,
+        PostEffectKind::FilmGrain,
+        PostEffectKind::Scanlines,
+        PostEffectKind::Pixelate,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            PostEffectKind::Bloom => "Bloom",
+            PostEffectKind::Fxaa => "FXAA",
+            PostEffectKind::ChromaticAberration => "Chromatic aberration",
+            PostEffectKind::Vignette => "Vignette",
+            PostEffectKind::FilmGrain => "Film grain",
+            PostEffectKind::Scanlines => "CRT scanlines",
+            PostEffectKind::Pixelate => "Pixelation",
+        }
+    }
+
+    // Fragment entry point in `post.wgsl` producing the effect's output
+    fn entry_point(self) -> &'static str {
+        match self {
+            PostEffectKind::Bloom => "fs_bloom",
+            PostEffectKind::Fxaa => "fs_fxaa",
+            PostEffectKind::ChromaticAberration => "fs_chromatic_aberration",
+            PostEffectKind::Vignette => "fs_vignette",
+            PostEffectKind::FilmGrain => "fs_film_grain",
+            PostEffectKind::Scanlines => "fs_scanlines",
+            PostEffectKind::Pixelate => "fs_pixelate",
+        }
+    }
+
+    // Whether the shader reads `uniforms.time`
+    fn animated(self) -> bool {
+        self == PostEffectKind::FilmGrain
+    }
+
+    // Up to four sliders, in the order of `uniforms.params` in the shader
+    fn params(self) -> &'static [Param] {
+        match self {
+            PostEffectKind::Bloom => &[
+                ("Threshold", 0.8, 0.0..=1.0),
+                ("Intensity", 0.8, 0.0..=4.0),
+                ("Spread", 1.5, 0.5..=4.0),
+            ],
+            PostEffectKind::Fxaa => &[
+                ("Span max", 8.0, 1.0..=16.0),
+                ("Reduce mul", 0.125, 0.0..=0.5),
+                ("Reduce min", 1.0 / 128.0, 0.0..=0.1),
+            ],
+            PostEffectKind::ChromaticAberration => &[("Strength (%)", 0.6, 0.0..=5.0)],
+            PostEffectKind::Vignette => &[
+                ("Intensity", 0.6, 0.0..=1.0),
+                ("Radius", 1.2, 0.2..=2.0),
+                ("Softness", 0.8, 0.01..=2.0),
+            ],
+            PostEffectKind::FilmGrain => &[("Intensity", 0.08, 0.0..=0.5), ("Grain size (px)", 1.0, 1.0..=8.0)],
+            PostEffectKind::Scanlines => &[
+                ("Intensity", 0.4, 0.0..=1.0),
+                ("Line period (px)", 3.0, 1.0..=16.0),
+                ("Curvature", 0.15, 0.0..=1.0),
+            ],
+            PostEffectKind::Pixelate => &[("Block size (px)", 6.0, 1.0..=64.0)],
+        }
+    }
+}
+
+pub struct PostEffect {
+    pub kind: PostEffectKind,
+    pub enabled: bool,
+    pub params: [f32; 4],
+}
+
+impl PostEffect {
+    fn new(kind: PostEffectKind) -> Self {
+        let mut params = [0.0; 4];
+        for (param, (_, default, _)) in params.iter_mut().zip(kind.params()) {
+            *param = *default;
+        }
+        Self {
+            kind,
+            enabled: false,
+            params,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostUniforms {
+    params: [f32; 4],
+    resolution: [f32; 2],
+    time: f32,
+    _padding: f32,
+}
+
+impl_uniform!(PostUniforms { params, resolution, time, _padding });
+
+struct Target {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+// Display-space effects applied in list order between tonemapping and egui.
+// The tonemapper writes into `input`; passes then ping-pong between two
+// textures and the last one writes the output.
+pub struct PostChain {
+    pub effects: Vec<PostEffect>,
+    format: wgpu::TextureFormat,
+    size: PhysicalSize<u32>,
+    // full resolution ping-pong pair; [0] is the chain's input
+    targets: [Target; 2],
+    // half resolution pair for the bloom highlights
+    bloom_targets: [Target; 2],
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    // by fragment entry point
+    pipelines: HashMap<&'static str, wgpu::RenderPipeline>,
+    // one per pass recorded in a frame, since queued writes all land before the
+    // command buffer runs
+    uniform_buffers: Vec<UniformBuffer<PostUniforms>>,
+}
+
+impl PostChain {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let source = include_str!("post.wgsl");
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Post Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        uniform::check_layout::<PostUniforms>("post.wgsl", source, 0, 0);
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                texture_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("post_bind_group_layout"),
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let entry_points = PostEffectKind::ALL
+            .iter()
+            .map(|kind| kind.entry_point())
+            .chain(["fs_bloom_extract", "fs_blur"]);
+        let pipelines = entry_points
+            .map(|entry_point| {
+                let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(entry_point),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: Some("vs_main"),
+                        buffers: &[],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: Some(entry_point),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format,
+                            blend: Some(wgpu::BlendState::REPLACE),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                    cache,
+                });
+                (entry_point, pipeline)
+            })
+            .collect();
+
+        let size = PhysicalSize::new(1, 1);
+        Self {
+            effects: PostEffectKind::ALL.into_iter().map(PostEffect::new).collect(),
+            format,
+            size,
+            targets: Self::create_targets(device, format, size, "Post Texture"),
+            bloom_targets: Self::create_targets(device, format, size, "Bloom Texture"),
+            bind_group_layout,
+            sampler,
+            pipelines,
+            uniform_buffers: Vec::new(),
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: PhysicalSize<u32>,
+        label: &str,
+    ) -> [Target; 2] {
+        [0, 1].map(|_| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size.width,
+                    height: size.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            Target { texture, view }
+        })
+    }
+
+    // True when at least one effect runs, so the scene has to go through `input`
+    pub fn is_active(&self) -> bool {
+        self.effects.iter().any(|effect| effect.enabled)
+    }
+
+    pub fn animating(&self) -> bool {
+        self.effects.iter().any(|effect| effect.enabled && effect.kind.animated())
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: PhysicalSize<u32>) {
+        if size == self.size {
+            return;
+        }
+        self.size = size;
+        self.targets = Self::create_targets(device, self.format, size, "Post Texture");
+        let half = PhysicalSize::new(size.width.div_ceil(2), size.height.div_ceil(2));
+        self.bloom_targets = Self::create_targets(device, self.format, half, "Bloom Texture");
+    }
+
+    // Where the tonemapped scene goes while the chain is active
+    pub fn input(&self) -> &wgpu::TextureView {
+        &self.targets[0].view
+    }
+
+    // Runs the enabled effects on `input`, writing the result to `output`
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        time: f32,
+    ) {
+        let enabled: Vec<(PostEffectKind, [f32; 4])> = self
+            .effects
+            .iter()
+            .filter(|effect| effect.enabled)
+            .map(|effect| (effect.kind, effect.params))
+            .collect();
+
+        // bloom adds an extract and two blur passes before its composite
+        let pass_count: usize = enabled
+            .iter()
+            .map(|(kind, _)| if *kind == PostEffectKind::Bloom { 4 } else { 1 })
+            .sum();
+        while self.uniform_buffers.len() < pass_count {
+            self.uniform_buffers.push(UniformBuffer::new(device, "Post Uniform Buffer"));
+        }
+
+        let mut pass_index = 0;
+        let mut current = 0;
+        for (i, &(kind, params)) in enabled.iter().enumerate() {
+            let source = &self.targets[current].view;
+            if kind == PostEffectKind::Bloom {
+                let [bloom_a, bloom_b] = &self.bloom_targets;
+                let spread = params[2];
+                let passes = [
+                    ("fs_bloom_extract", source, &bloom_a.view, params),
+                    ("fs_blur", &bloom_a.view, &bloom_b.view, [1.0, 0.0, spread, 0.0]),
+                    ("fs_blur", &bloom_b.view, &bloom_a.view, [0.0, 1.0, spread, 0.0]),
+                ];
+                for (entry_point, pass_source, pass_target, pass_params) in passes {
+                    let size = bloom_a.texture.size();
+                    self.uniform_buffers[pass_index].write(
+                        queue,
+                        PostUniforms {
+                            params: pass_params,
+                            resolution: [size.width as f32, size.height as f32],
+                            time,
+                            _padding: 0.0,
+                        },
+                    );
+                    self.pass(device, encoder, pass_index, entry_point, (pass_source, pass_source), pass_target);
+                    pass_index += 1;
+                }
+            }
+
+            let last = i + 1 == enabled.len();
+            let target = if last { output } else { &self.targets[1 - current].view };
+            let secondary = if kind == PostEffectKind::Bloom { &self.bloom_targets[0].view } else { source };
+            self.uniform_buffers[pass_index].write(
+                queue,
+                PostUniforms {
+                    params,
+                    resolution: [self.size.width as f32, self.size.height as f32],
+                    time,
+                    _padding: 0.0,
+                },
+            );
+            self.pass(device, encoder, pass_index, kind.entry_point(), (source, secondary), target);
+            pass_index += 1;
+            current = 1 - current;
+        }
+    }
+
+    // Draws one pass with the uniforms already written to buffer `pass_index`
+    fn pass(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pass_index: usize,
+        entry_point: &str,
+        (source, secondary): (&wgpu::TextureView, &wgpu::TextureView),
+        target: &wgpu::TextureView,
+    ) {
+        let uniform_buffer = &self.uniform_buffers[pass_index];
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(secondary),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+            label: Some("post_bind_group"),
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(entry_point),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipelines[entry_point]);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    // (effect, parameters) of the enabled effects in chain order
+    pub fn metadata(&self) -> Option<String> {
+        let effects: Vec<String> = self
+            .effects
+            .iter()
+            .filter(|effect| effect.enabled)
+            .map(|effect| {
+                let count = effect.kind.params().len();
+                format!("{} {:?}", effect.kind.label(), &effect.params[..count])
+            })
+            .collect();
+        (!effects.is_empty()).then(|| effects.join(", "))
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Drag ☰ to reorder");
+        let mut moved = None;
+        for (i, effect) in self.effects.iter_mut().enumerate() {
+            ui.push_id(i, |ui| {
+                let row = ui
+                    .horizontal(|ui| {
+                        ui.dnd_drag_source(egui::Id::new(("post_effect", i)), i, |ui| {
+                            ui.label("☰");
+                        });
+                        ui.checkbox(&mut effect.enabled, effect.kind.label());
+                    })
+                    .response;
+                // The upper half of a row drops above it, the lower half below,
+                // so the last row can take an effect after it
+                let below = ui.ctx().pointer_latest_pos().is_some_and(|pos| pos.y > row.rect.center().y);
+                let slot = i + below as usize;
+                // Line where the dragged effect would land
+                if row.dnd_hover_payload::<usize>().is_some() {
+                    let y = if below { row.rect.bottom() } else { row.rect.top() };
+                    ui.painter().hline(row.rect.x_range(), y, ui.visuals().selection.stroke);
+                }
+                if let Some(from) = row.dnd_release_payload::<usize>() {
+                    moved = Some((*from, slot));
+                }
+                if effect.enabled {
+                    ui.indent("params", |ui| {
+                        for (value, (label, _, range)) in effect.params.iter_mut().zip(effect.kind.params()) {
+                            ui.add(egui::Slider::new(value, range.clone()).text(*label));
+                        }
+                    });
+                }
+            });
+        }
+        if let Some((from, slot)) = moved {
+            move_to_slot(&mut self.effects, from, slot);
+        }
+    }
+}
+
+// Moves `items[from]` into the gap before `items[slot]` (`slot == len` is the end).
+// Slots past `from` shift up by one once the item has been taken out.
+fn move_to_slot<T>(items: &mut Vec<T>, from: usize, slot: usize) {
+    let item = items.remove(from);
+    items.insert(slot - (from < slot) as usize, item);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moved(from: usize, slot: usize) -> Vec<u32> {
+        let mut items = vec![0, 1, 2, 3];
+        move_to_slot(&mut items, from, slot);
+        items
+    }
+
+    #[test]
+    fn move_to_slot_reorders() {
+        // the gaps either side of an item leave it in place
+        for i in 0..4 {
+            assert_eq!(moved(i, i), [0, 1, 2, 3]);
+            assert_eq!(moved(i, i + 1), [0, 1, 2, 3]);
+        }
+        assert_eq!(moved(0, 4), [1, 2, 3, 0]);
+        assert_eq!(moved(1, 4), [0, 2, 3, 1]);
+        assert_eq!(moved(3, 0), [3, 0, 1, 2]);
+        assert_eq!(moved(0, 2), [1, 0, 2, 3]);
+        assert_eq!(moved(2, 1), [0, 2, 1, 3]);
+    }
+}
@@ -0,0 +1,164 @@
+// post.wgsl
+// Every post effect is one fragment entry point sampling `source`
+struct Uniforms {
+    // effect parameters, see `PostEffectKind::params`
+    params: vec4<f32>,
+    // target size in physical pixels
+    resolution: vec2<f32>,
+    time: f32,
+    _padding: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+// the blurred highlights for `fs_bloom`, otherwise `source` again
+@group(0) @binding(2)
+var secondary: texture_2d<f32>;
+@group(0) @binding(3)
+var linear_sampler: sampler;
+
+// Renders a full-screen triangle without vertex data
+@vertex
+fn vs_main(@builtin(vertex_index) vert_index: u32) -> @builtin(position) vec4<f32> {
+    let pos = array(
+        vec2(-1.0, -1.0),
+        vec2(3.0, -1.0),
+        vec2(-1.0, 3.0),
+    );
+    return vec4(pos[vert_index], 0.0, 1.0);
+}
+
+fn uv_of(frag_position: vec4<f32>) -> vec2<f32> {
+    return frag_position.xy / uniforms.resolution;
+}
+
+fn sample_source(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(source, linear_sampler, uv, 0.0);
+}
+
+// Perceptual luma of a linear color
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(sqrt(max(color, vec3(0.0))), vec3(0.299, 0.587, 0.114));
+}
+
+fn hash(p: vec2<f32>) -> f32 {
+    let q = fract(p * vec2(123.34, 456.21));
+    let r = q + dot(q, q + 45.32);
+    return fract(r.x * r.y);
+}
+
+// params: threshold, -, -, -
+@fragment
+fn fs_bloom_extract(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let color = sample_source(uv_of(frag_position)).rgb;
+    let brightness = max(color.r, max(color.g, color.b));
+    let weight = max(brightness - uniforms.params.x, 0.0) / max(brightness, 1e-4);
+    return vec4(color * weight, 1.0);
+}
+
+// params: direction x, direction y, spread in texels, -
+@fragment
+fn fs_blur(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let uv = uv_of(frag_position);
+    let step = uniforms.params.xy * uniforms.params.z / uniforms.resolution;
+    let weights = array(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+    var color = sample_source(uv).rgb * weights[0];
+    for (var i = 1; i < 5; i++) {
+        let offset = step * f32(i);
+        color += (sample_source(uv + offset).rgb + sample_source(uv - offset).rgb) * weights[i];
+    }
+    return vec4(color, 1.0);
+}
+
+// params: threshold, intensity, spread, -
+@fragment
+fn fs_bloom(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let uv = uv_of(frag_position);
+    let color = sample_source(uv);
+    let bloom = textureSampleLevel(secondary, linear_sampler, uv, 0.0).rgb;
+    return vec4(color.rgb + bloom * uniforms.params.y, color.a);
+}
+
+// FXAA in the style of the original console version.
+// params: span max in pixels, reduce mul, reduce min, -
+@fragment
+fn fs_fxaa(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let texel = 1.0 / uniforms.resolution;
+    let uv = uv_of(frag_position);
+    let center = sample_source(uv);
+    let luma_nw = luma(sample_source(uv + vec2(-1.0, -1.0) * texel).rgb);
+    let luma_ne = luma(sample_source(uv + vec2(1.0, -1.0) * texel).rgb);
+    let luma_sw = luma(sample_source(uv + vec2(-1.0, 1.0) * texel).rgb);
+    let luma_se = luma(sample_source(uv + vec2(1.0, 1.0) * texel).rgb);
+    let luma_m = luma(center.rgb);
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var dir = vec2(-((luma_nw + luma_ne) - (luma_sw + luma_se)), (luma_nw + luma_sw) - (luma_ne + luma_se));
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * uniforms.params.y, uniforms.params.z);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2(-uniforms.params.x), vec2(uniforms.params.x)) * texel;
+
+    let rgb_a = 0.5 * (sample_source(uv + dir * (1.0 / 3.0 - 0.5)).rgb + sample_source(uv + dir * (2.0 / 3.0 - 0.5)).rgb);
+    let rgb_b = rgb_a * 0.5 + 0.25 * (sample_source(uv - dir * 0.5).rgb + sample_source(uv + dir * 0.5).rgb);
+    let luma_b = luma(rgb_b);
+    if luma_b < luma_min || luma_b > luma_max {
+        return vec4(rgb_a, center.a);
+    }
+    return vec4(rgb_b, center.a);
+}
+
+// params: strength in percent of the image size, -, -, -
+@fragment
+fn fs_chromatic_aberration(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let uv = uv_of(frag_position);
+    let offset = (uv - 0.5) * uniforms.params.x * 0.01;
+    let center = sample_source(uv);
+    let r = sample_source(uv + offset).r;
+    let b = sample_source(uv - offset).b;
+    return vec4(r, center.g, b, center.a);
+}
+
+// params: intensity, radius, softness, -
+@fragment
+fn fs_vignette(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let uv = uv_of(frag_position);
+    let color = sample_source(uv);
+    // distance from the center, 1 at the middle of each edge
+    let distance = length((uv - 0.5) * 2.0);
+    let falloff = smoothstep(uniforms.params.y, uniforms.params.y - uniforms.params.z, distance);
+    return vec4(color.rgb * mix(1.0, falloff, uniforms.params.x), color.a);
+}
+
+// params: intensity, grain size in pixels, -, -
+@fragment
+fn fs_film_grain(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let color = sample_source(uv_of(frag_position));
+    let cell = floor(frag_position.xy / max(uniforms.params.y, 1.0));
+    let noise = hash(cell + fract(uniforms.time * 7.31) * 1000.0) - 0.5;
+    return vec4(max(color.rgb + noise * uniforms.params.x, vec3(0.0)), color.a);
+}
+
+// params: intensity, line period in pixels, curvature, -
+@fragment
+fn fs_scanlines(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let centered = uv_of(frag_position) - 0.5;
+    // barrel distortion of a curved tube
+    let uv = centered * (1.0 + dot(centered, centered) * uniforms.params.z) + 0.5;
+    let inside = all(uv >= vec2(0.0)) && all(uv <= vec2(1.0));
+    let color = sample_source(uv);
+    let line = 0.5 + 0.5 * sin(uv.y * uniforms.resolution.y * 6.2831853 / max(uniforms.params.y, 1.0));
+    let shade = 1.0 - uniforms.params.x * (1.0 - line);
+    return select(vec4(0.0, 0.0, 0.0, 1.0), vec4(color.rgb * shade, color.a), inside);
+}
+
+// params: block size in pixels, -, -, -
+@fragment
+fn fs_pixelate(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = max(uniforms.params.x, 1.0);
+    let center = (floor(frag_position.xy / size) + 0.5) * size;
+    let texel = min(vec2<i32>(center), vec2<i32>(uniforms.resolution) - 1);
+    return textureLoad(source, texel, 0);
+}
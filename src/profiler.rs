@@ -0,0 +1,306 @@
+use egui_wgpu::wgpu;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// frames kept for the graphs and averages
+const HISTORY: usize = 240;
+// two timestamps per scope
+const MAX_GPU_SCOPES: u32 = 32;
+// frames of GPU results that may be in flight at once
+const READBACK_BUFFERS: usize = 3;
+
+// (label, milliseconds) of one frame
+type Timings = Vec<(String, f32)>;
+
+struct Readback {
+    buffer: wgpu::Buffer,
+    // scope labels in query order; empty while the buffer is free
+    labels: Vec<String>,
+    // set by map_async once the results can be read
+    mapped: Arc<AtomicBool>,
+}
+
+// Timestamp queries around GPU work. Results arrive a few frames late, read back
+// through a small ring of buffers so the CPU never waits for the GPU.
+struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readbacks: Vec<Readback>,
+    // timestamps between passes need TIMESTAMP_QUERY_INSIDE_ENCODERS; without it an
+    // empty compute pass writes them
+    inside_encoders: bool,
+    // nanoseconds per timestamp tick
+    period: f32,
+    scopes: Vec<String>,
+    // whether the open scope got a query, false once the budget is used up
+    in_scope: bool,
+    // readback holding this frame's results, to map after submit
+    submitted: Option<usize>,
+}
+
+impl GpuTimer {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let size = (MAX_GPU_SCOPES * 2) as u64 * wgpu::QUERY_SIZE as u64;
+        let readbacks = (0..READBACK_BUFFERS)
+            .map(|_| Readback {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Timestamp Readback Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+                labels: Vec::new(),
+                mapped: Arc::new(AtomicBool::new(false)),
+            })
+            .collect();
+        Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Timestamp Queries"),
+                ty: wgpu::QueryType::Timestamp,
+                count: MAX_GPU_SCOPES * 2,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp Resolve Buffer"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readbacks,
+            inside_encoders: device
+                .features()
+                .contains(wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS),
+            period: queue.get_timestamp_period(),
+            scopes: Vec::new(),
+            in_scope: false,
+            submitted: None,
+        }
+    }
+
+    fn write_timestamp(&self, encoder: &mut wgpu::CommandEncoder, index: u32) {
+        if self.inside_encoders {
+            encoder.write_timestamp(&self.query_set, index);
+        } else {
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Timestamp"),
+                timestamp_writes: Some(wgpu::ComputePassTimestampWrites {
+                    query_set: &self.query_set,
+                    beginning_of_pass_write_index: Some(index),
+                    end_of_pass_write_index: None,
+                }),
+            });
+        }
+    }
+
+    // Finished frames, oldest first
+    fn collect(&mut self, device: &wgpu::Device) -> Vec<Timings> {
+        device.poll(wgpu::Maintain::Poll);
+        let mut frames = Vec::new();
+        for readback in &mut self.readbacks {
+            if !readback.mapped.swap(false, Ordering::Acquire) {
+                continue;
+            }
+            let count = readback.labels.len() * 2;
+            let timings = {
+                let data = readback.buffer.slice(..).get_mapped_range();
+                let ticks: &[u64] = bytemuck::cast_slice(&data[..count * wgpu::QUERY_SIZE as usize]);
+                readback
+                    .labels
+                    .drain(..)
+                    .zip(ticks.chunks_exact(2))
+                    .map(|(label, pair)| {
+                        let ticks = pair[1].saturating_sub(pair[0]);
+                        (label, ticks as f32 * self.period / 1_000_000.0)
+                    })
+                    .collect()
+            };
+            readback.buffer.unmap();
+            frames.push(timings);
+        }
+        frames
+    }
+}
+
+// CPU and GPU frame timings, shown in the profiler window
+pub struct Profiler {
+    pub open: bool,
+    gpu: Option<GpuTimer>,
+    cpu_frame: Timings,
+    cpu_history: VecDeque<Timings>,
+    gpu_history: VecDeque<Timings>,
+}
+
+impl Profiler {
+    // GPU timing needs TIMESTAMP_QUERY on the device; without it only CPU times are kept
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let gpu = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| GpuTimer::new(device, queue));
+        Self {
+            open: false,
+            gpu,
+            cpu_frame: Vec::new(),
+            cpu_history: VecDeque::new(),
+            gpu_history: VecDeque::new(),
+        }
+    }
+
+    // Picks up GPU results of earlier frames; call before recording any scope
+    pub fn begin_frame(&mut self, device: &wgpu::Device) {
+        // left over from a frame that was skipped
+        self.cpu_frame.clear();
+        let Some(gpu) = &mut self.gpu else {
+            return;
+        };
+        for timings in gpu.collect(device) {
+            push_history(&mut self.gpu_history, timings);
+        }
+        gpu.scopes.clear();
+    }
+
+    pub fn cpu(&mut self, label: &str, duration: Duration) {
+        self.cpu_frame.push((label.to_string(), duration.as_secs_f32() * 1000.0));
+    }
+
+    // Scopes must not nest; extra scopes past the query budget are not timed
+    pub fn begin_gpu(&mut self, encoder: &mut wgpu::CommandEncoder, label: &str) {
+        let Some(gpu) = &mut self.gpu else {
+            return;
+        };
+        let index = gpu.scopes.len() as u32;
+        gpu.in_scope = index < MAX_GPU_SCOPES;
+        if gpu.in_scope {
+            gpu.write_timestamp(encoder, index * 2);
+            gpu.scopes.push(label.to_string());
+        }
+    }
+
+    pub fn end_gpu(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(gpu) = &mut self.gpu else {
+            return;
+        };
+        if std::mem::take(&mut gpu.in_scope) {
+            gpu.write_timestamp(encoder, gpu.scopes.len() as u32 * 2 - 1);
+        }
+    }
+
+    // Copies this frame's timestamps out; call last before finishing the encoder
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(gpu) = &mut self.gpu else {
+            return;
+        };
+        let count = gpu.scopes.len() as u32 * 2;
+        // the frame goes untimed when every buffer is still waiting for the GPU
+        let free = gpu.readbacks.iter().position(|readback| readback.labels.is_empty());
+        let (Some(index), true) = (free, count > 0) else {
+            return;
+        };
+        let size = count as u64 * wgpu::QUERY_SIZE as u64;
+        encoder.resolve_query_set(&gpu.query_set, 0..count, &gpu.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&gpu.resolve_buffer, 0, &gpu.readbacks[index].buffer, 0, size);
+        gpu.readbacks[index].labels = std::mem::take(&mut gpu.scopes);
+        gpu.submitted = Some(index);
+    }
+
+    // Starts reading back the resolved timestamps; call after submitting the encoder
+    pub fn end_frame(&mut self) {
+        push_history(&mut self.cpu_history, std::mem::take(&mut self.cpu_frame));
+        let Some(gpu) = &mut self.gpu else {
+            return;
+        };
+        if let Some(index) = gpu.submitted.take() {
+            let mapped = gpu.readbacks[index].mapped.clone();
+            gpu.readbacks[index]
+                .buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    if result.is_ok() {
+                        mapped.store(true, Ordering::Release);
+                    }
+                });
+        }
+    }
+
+    pub fn window(&mut self, ctx: &egui::Context) {
+        let mut open = self.open;
+        egui::Window::new("Profiler")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                section(ui, "CPU", &self.cpu_history, egui::Color32::LIGHT_BLUE);
+                ui.separator();
+                if self.gpu.is_some() {
+                    section(ui, "GPU", &self.gpu_history, egui::Color32::LIGHT_GREEN);
+                } else {
+                    ui.label("GPU timing needs timestamp queries, which this adapter does not support");
+                }
+            });
+        self.open = open;
+    }
+}
+
+fn push_history(history: &mut VecDeque<Timings>, timings: Timings) {
+    if history.len() == HISTORY {
+        history.pop_front();
+    }
+    history.push_back(timings);
+}
+
+// Rolling graph of the frame totals plus a table of the latest frame's parts
+fn section(ui: &mut egui::Ui, title: &str, history: &VecDeque<Timings>, color: egui::Color32) {
+    let totals: Vec<f32> = history
+        .iter()
+        .map(|timings| timings.iter().map(|(_, ms)| ms).sum())
+        .collect();
+    let Some(&last) = totals.last() else {
+        ui.label(format!("{title}: waiting for data"));
+        return;
+    };
+    let average = totals.iter().sum::<f32>() / totals.len() as f32;
+    ui.label(format!("{title}: {last:.2} ms, average {average:.2} ms"));
+    graph(ui, &totals, color);
+
+    // averages over the frames that contain each label
+    let latest = history.back().unwrap();
+    egui::Grid::new(title).num_columns(3).striped(true).show(ui, |ui| {
+        ui.strong("Pass");
+        ui.strong("Last (ms)");
+        ui.strong("Average (ms)");
+        ui.end_row();
+        for (label, ms) in latest {
+            let samples: Vec<f32> = history
+                .iter()
+                .flat_map(|timings| timings.iter().filter(|(other, _)| other == label).map(|(_, ms)| *ms))
+                .collect();
+            ui.label(label);
+            ui.monospace(format!("{ms:.3}"));
+            ui.monospace(format!("{:.3}", samples.iter().sum::<f32>() / samples.len() as f32));
+            ui.end_row();
+        }
+    });
+}
+
+fn graph(ui: &mut egui::Ui, values: &[f32], color: egui::Color32) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 60.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    let max = values.iter().copied().fold(1.0, f32::max);
+    let step = rect.width() / (HISTORY - 1) as f32;
+    // newest sample on the right edge
+    let offset = (HISTORY - values.len()) as f32 * step;
+    let points = values
+        .iter()
+        .enumerate()
+        .map(|(i, ms)| egui::pos2(rect.left() + offset + i as f32 * step, rect.bottom() - ms / max * rect.height()))
+        .collect();
+    painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, color)));
+    painter.text(
+        rect.left_top() + egui::vec2(4.0, 2.0),
+        egui::Align2::LEFT_TOP,
+        format!("{max:.1} ms"),
+        egui::FontId::monospace(10.0),
+        ui.visuals().weak_text_color(),
+    );
+}
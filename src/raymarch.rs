@@ -0,0 +1,280 @@
+use crate::layer::{FrameContext, Layer, RenderTarget};
+use crate::uniform::{self, impl_uniform, UniformBuffer};
+use egui_wgpu::wgpu;
+use std::any::Any;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fractal3d {
+    Mandelbulb,
+    Mandelbox,
+    QuaternionJulia,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaymarchParams {
+    pub fractal: Fractal3d,
+    pub power: f32,
+    pub box_scale: f32,
+    pub julia_c: [f32; 4],
+    pub iterations: u32,
+    pub max_steps: u32,
+    pub soft_shadows: bool,
+    pub ambient_occlusion: bool,
+}
+
+impl Default for RaymarchParams {
+    fn default() -> Self {
+        Self {
+            fractal: Fractal3d::Mandelbulb,
+            power: 8.0,
+            box_scale: -1.5,
+            julia_c: [-0.2, 0.6, 0.2, 0.2],
+            iterations: 12,
+            max_steps: 160,
+            soft_shadows: true,
+            ambient_occlusion: true,
+        }
+    }
+}
+
+impl RaymarchParams {
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("Fractal")
+            .selected_text(format!("{:?}", self.fractal))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.fractal, Fractal3d::Mandelbulb, "Mandelbulb");
+                ui.selectable_value(&mut self.fractal, Fractal3d::Mandelbox, "Mandelbox");
+                ui.selectable_value(&mut self.fractal, Fractal3d::QuaternionJulia, "Quaternion Julia");
+            });
+
+        match self.fractal {
+            Fractal3d::Mandelbulb => {
+                ui.add(egui::Slider::new(&mut self.power, 2.0..=16.0).text("Power"));
+            }
+            Fractal3d::Mandelbox => {
+                ui.add(egui::Slider::new(&mut self.box_scale, -3.0..=3.0).text("Scale"));
+            }
+            Fractal3d::QuaternionJulia => {
+                ui.horizontal(|ui| {
+                    ui.label("c");
+                    for v in self.julia_c.iter_mut() {
+                        ui.add(egui::DragValue::new(v).speed(0.01).range(-1.5..=1.5));
+                    }
+                });
+            }
+        }
+
+        ui.add(egui::Slider::new(&mut self.iterations, 1..=32).text("Iterations"));
+        ui.add(egui::Slider::new(&mut self.max_steps, 16..=512).text("Max steps"));
+        ui.checkbox(&mut self.soft_shadows, "Soft shadows");
+        ui.checkbox(&mut self.ambient_occlusion, "Ambient occlusion");
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RaymarchUniforms {
+    eye: [f32; 3],
+    fov_y: f32,
+    forward: [f32; 3],
+    time: f32,
+    right: [f32; 3],
+    power: f32,
+    up: [f32; 3],
+    box_scale: f32,
+    julia_c: [f32; 4],
+    resolution: [f32; 2],
+    fractal: u32,
+    iterations: u32,
+    max_steps: u32,
+    flags: u32,
+    _padding: [u32; 2],
+}
+
+impl_uniform!(RaymarchUniforms {
+    eye,
+    fov_y,
+    forward,
+    time,
+    right,
+    power,
+    up,
+    box_scale,
+    julia_c,
+    resolution,
+    fractal,
+    iterations,
+    max_steps,
+    flags,
+    _padding,
+});
+
+pub struct RaymarchRenderer {
+    pub params: RaymarchParams,
+    format: wgpu::TextureFormat,
+    cache: Option<wgpu::PipelineCache>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: UniformBuffer<RaymarchUniforms>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl RaymarchRenderer {
+    pub fn new(device: &wgpu::Device, target_format: wgpu::TextureFormat, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let source = include_str!("raymarch.wgsl");
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Raymarch Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        uniform::check_layout::<RaymarchUniforms>("raymarch.wgsl", source, 0, 0);
+        let uniform_buffer = UniformBuffer::new(device, "Raymarch Uniform Buffer");
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("raymarch_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.binding(),
+            }],
+            label: Some("raymarch_bind_group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Raymarch Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, &shader, target_format, 1, cache);
+
+        Self {
+            params: RaymarchParams::default(),
+            format: target_format,
+            cache: cache.cloned(),
+            shader,
+            pipeline_layout,
+            pipeline,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Raymarch Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache,
+        })
+    }
+}
+
+impl Layer for RaymarchRenderer {
+    fn name(&self) -> &str {
+        "3D fractal"
+    }
+
+    fn restore(&mut self, old: Box<dyn Any>) {
+        let Ok(old) = old.downcast::<Self>() else {
+            return;
+        };
+        self.params = old.params;
+    }
+
+    // the light circles the scene
+    fn animating(&self) -> bool {
+        true
+    }
+
+    fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.pipeline = Self::create_pipeline(device, &self.pipeline_layout, &self.shader, self.format, sample_count, self.cache.as_ref());
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        let camera = frame.camera;
+        let (forward, right, up) = camera.basis();
+        let params = &self.params;
+        let uniforms = RaymarchUniforms {
+            eye: camera.eye().to_array(),
+            fov_y: camera.fov_y,
+            forward: forward.to_array(),
+            time: frame.time,
+            right: right.to_array(),
+            power: params.power,
+            up: up.to_array(),
+            box_scale: params.box_scale,
+            julia_c: params.julia_c,
+            resolution: [frame.size.width as f32, frame.size.height as f32],
+            fractal: params.fractal as u32,
+            iterations: params.iterations,
+            max_steps: params.max_steps,
+            flags: params.soft_shadows as u32 | (params.ambient_occlusion as u32) << 1,
+            _padding: [0; 2],
+        };
+        self.uniform_buffer.write(frame.queue, uniforms);
+    }
+
+    fn render(&mut self, _frame: &FrameContext, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Raymarch Pass"),
+            color_attachments: &[Some(target.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn metadata(&self) -> Option<String> {
+        Some(format!("{:?}", self.params))
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        self.params.ui(ui);
+    }
+}
@@ -0,0 +1,202 @@
+struct Uniforms {
+    eye: vec3<f32>,
+    // vertical field of view in radians
+    fov_y: f32,
+    forward: vec3<f32>,
+    time: f32,
+    right: vec3<f32>,
+    power: f32,
+    up: vec3<f32>,
+    box_scale: f32,
+    julia_c: vec4<f32>,
+    resolution: vec2<f32>,
+    // 0 = Mandelbulb, 1 = Mandelbox, 2 = quaternion Julia
+    fractal: u32,
+    iterations: u32,
+    max_steps: u32,
+    // bit 0: soft shadows, bit 1: ambient occlusion
+    flags: u32,
+    _padding: vec2<u32>,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+const SOFT_SHADOWS: u32 = 1u;
+const AMBIENT_OCCLUSION: u32 = 2u;
+const MAX_DISTANCE: f32 = 20.0;
+const SURFACE_EPSILON: f32 = 0.0005;
+
+// Renders a full-screen triangle without vertex data
+@vertex
+fn vs_main(@builtin(vertex_index) vert_index: u32) -> @builtin(position) vec4<f32> {
+    let pos = array(
+        vec2(-1.0, -1.0),
+        vec2(3.0, -1.0),
+        vec2(-1.0, 3.0),
+    );
+    return vec4(pos[vert_index], 0.0, 1.0);
+}
+
+fn de_mandelbulb(p: vec3<f32>) -> f32 {
+    var z = p;
+    var dr = 1.0;
+    var r = 0.0;
+    for (var i = 0u; i < uniforms.iterations; i++) {
+        r = length(z);
+        if r > 2.0 {
+            break;
+        }
+        let theta = acos(clamp(z.z / r, -1.0, 1.0)) * uniforms.power;
+        let phi = atan2(z.y, z.x) * uniforms.power;
+        dr = pow(r, uniforms.power - 1.0) * uniforms.power * dr + 1.0;
+        let zr = pow(r, uniforms.power);
+        z = zr * vec3(sin(theta) * cos(phi), sin(phi) * sin(theta), cos(theta)) + p;
+    }
+    return 0.5 * log(max(r, 1e-6)) * r / dr;
+}
+
+fn de_mandelbox(p: vec3<f32>) -> f32 {
+    var z = p;
+    var dr = 1.0;
+    let scale = uniforms.box_scale;
+    for (var i = 0u; i < uniforms.iterations; i++) {
+        // box fold
+        z = clamp(z, vec3(-1.0), vec3(1.0)) * 2.0 - z;
+        // sphere fold
+        let r2 = dot(z, z);
+        if r2 < 0.25 {
+            z *= 4.0;
+            dr *= 4.0;
+        } else if r2 < 1.0 {
+            z /= r2;
+            dr /= r2;
+        }
+        z = scale * z + p;
+        dr = dr * abs(scale) + 1.0;
+    }
+    return length(z) / abs(dr);
+}
+
+fn qmul(a: vec4<f32>, b: vec4<f32>) -> vec4<f32> {
+    return vec4(
+        a.x * b.x - dot(a.yzw, b.yzw),
+        a.x * b.yzw + b.x * a.yzw + cross(a.yzw, b.yzw),
+    );
+}
+
+fn de_julia(p: vec3<f32>) -> f32 {
+    var z = vec4(p, 0.0);
+    var dz = vec4(1.0, 0.0, 0.0, 0.0);
+    for (var i = 0u; i < uniforms.iterations; i++) {
+        dz = 2.0 * qmul(z, dz);
+        z = qmul(z, z) + uniforms.julia_c;
+        if dot(z, z) > 16.0 {
+            break;
+        }
+    }
+    let r = length(z);
+    return 0.5 * r * log(max(r, 1e-6)) / length(dz);
+}
+
+fn scene(p: vec3<f32>) -> f32 {
+    switch uniforms.fractal {
+        case 1u: {
+            return de_mandelbox(p);
+        }
+        case 2u: {
+            return de_julia(p);
+        }
+        default: {
+            return de_mandelbulb(p);
+        }
+    }
+}
+
+fn normal(p: vec3<f32>) -> vec3<f32> {
+    let e = vec2(SURFACE_EPSILON, 0.0);
+    return normalize(vec3(
+        scene(p + e.xyy) - scene(p - e.xyy),
+        scene(p + e.yxy) - scene(p - e.yxy),
+        scene(p + e.yyx) - scene(p - e.yyx),
+    ));
+}
+
+fn soft_shadow(origin: vec3<f32>, dir: vec3<f32>) -> f32 {
+    var shade = 1.0;
+    var t = 0.01;
+    for (var i = 0; i < 64; i++) {
+        let d = scene(origin + dir * t);
+        shade = min(shade, 8.0 * d / t);
+        t += clamp(d, 0.005, 0.2);
+        if d < SURFACE_EPSILON || t > MAX_DISTANCE {
+            break;
+        }
+    }
+    return clamp(shade, 0.0, 1.0);
+}
+
+fn ambient_occlusion(p: vec3<f32>, n: vec3<f32>) -> f32 {
+    var occlusion = 0.0;
+    var weight = 1.0;
+    for (var i = 1; i <= 5; i++) {
+        let h = 0.02 * f32(i);
+        occlusion += weight * (h - scene(p + n * h));
+        weight *= 0.6;
+    }
+    return clamp(1.0 - 6.0 * occlusion, 0.0, 1.0);
+}
+
+fn background(dir: vec3<f32>) -> vec3<f32> {
+    return mix(vec3(0.05, 0.05, 0.08), vec3(0.3, 0.4, 0.6), 0.5 + 0.5 * dir.y);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32> {
+    // Pixel to camera ray; frag y grows downwards
+    let uv = (frag_position.xy - 0.5 * uniforms.resolution) / uniforms.resolution.y;
+    let half_height = tan(0.5 * uniforms.fov_y);
+    let dir = normalize(
+        uniforms.forward + (uv.x * uniforms.right - uv.y * uniforms.up) * 2.0 * half_height
+    );
+
+    var t = 0.0;
+    var hit = false;
+    for (var i = 0u; i < uniforms.max_steps; i++) {
+        let d = scene(uniforms.eye + dir * t);
+        if d < SURFACE_EPSILON * max(t, 1.0) {
+            hit = true;
+            break;
+        }
+        t += d;
+        if t > MAX_DISTANCE {
+            break;
+        }
+    }
+
+    if !hit {
+        return vec4(background(dir), 1.0);
+    }
+
+    let p = uniforms.eye + dir * t;
+    let n = normal(p);
+    let light = normalize(vec3(cos(uniforms.time * 0.3), 0.8, sin(uniforms.time * 0.3)));
+
+    var shadow = 1.0;
+    if (uniforms.flags & SOFT_SHADOWS) != 0u {
+        shadow = soft_shadow(p + n * SURFACE_EPSILON * 4.0, light);
+    }
+    var occlusion = 1.0;
+    if (uniforms.flags & AMBIENT_OCCLUSION) != 0u {
+        occlusion = ambient_occlusion(p, n);
+    }
+
+    let albedo = 0.5 + 0.5 * cos(vec3(0.0, 0.6, 1.0) + length(p) * 3.0);
+    let diffuse = max(dot(n, light), 0.0) * shadow;
+    let ambient = 0.25 * occlusion;
+    let color = albedo * (diffuse + ambient);
+
+    // fog towards the background
+    let fog = exp(-0.008 * t * t);
+    return vec4(mix(background(dir), color, fog), 1.0);
+}
@@ -0,0 +1,324 @@
+use crate::screenshot;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use winit::dpi::PhysicalSize;
+
+const RECORDING_DIR: &str = "recordings";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordFormat {
+    PngSequence,
+    Gif,
+    Y4m,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RecordSettings {
+    pub format: RecordFormat,
+    pub fps: u32,
+    pub duration: f32,
+    pub size: PhysicalSize<u32>,
+}
+
+impl Default for RecordSettings {
+    fn default() -> Self {
+        Self {
+            format: RecordFormat::PngSequence,
+            fps: 30,
+            duration: 5.0,
+            size: PhysicalSize::new(1280, 720),
+        }
+    }
+}
+
+enum Sink {
+    Png(PathBuf),
+    Gif(BufWriter<File>),
+    Y4m(BufWriter<File>),
+}
+
+struct Recording {
+    sink: Sink,
+    path: PathBuf,
+    // scene time of the first frame
+    start_time: f32,
+    frame: u32,
+    total_frames: u32,
+}
+
+// Captures consecutive scene frames at a fixed simulated frame rate: while a
+// recording runs, scene time advances by exactly 1 / fps per rendered frame no
+// matter how long the frame took in real time.
+pub struct Recorder {
+    pub settings: RecordSettings,
+    recording: Option<Recording>,
+    status: Option<String>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self {
+            settings: RecordSettings::default(),
+            recording: None,
+            status: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    // Resolution the scene must be rendered at while recording
+    pub fn size(&self) -> Option<PhysicalSize<u32>> {
+        self.recording.as_ref().map(|_| self.settings.size)
+    }
+
+    // (scene time, time step) of the frame about to be rendered
+    pub fn frame_time(&self) -> Option<(f32, f32)> {
+        let recording = self.recording.as_ref()?;
+        let dt = 1.0 / self.settings.fps as f32;
+        Some((recording.start_time + recording.frame as f32 * dt, dt))
+    }
+
+    fn start(&mut self, time: f32) -> Result<(), String> {
+        let settings = self.settings;
+        let stamp = screenshot::timestamp();
+        std::fs::create_dir_all(RECORDING_DIR).map_err(|err| err.to_string())?;
+        let create = |path: &PathBuf| -> Result<BufWriter<File>, String> {
+            File::create(path)
+                .map(BufWriter::new)
+                .map_err(|err| format!("failed to create {}: {err}", path.display()))
+        };
+
+        let (path, sink) = match settings.format {
+            RecordFormat::PngSequence => {
+                let dir = PathBuf::from(RECORDING_DIR).join(format!("recording-{stamp}"));
+                std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+                (dir.clone(), Sink::Png(dir))
+            }
+            RecordFormat::Gif => {
+                let path = PathBuf::from(RECORDING_DIR).join(format!("recording-{stamp}.gif"));
+                let mut file = create(&path)?;
+                gif_header(&mut file, settings.size).map_err(|err| err.to_string())?;
+                (path, Sink::Gif(file))
+            }
+            RecordFormat::Y4m => {
+                let path = PathBuf::from(RECORDING_DIR).join(format!("recording-{stamp}.y4m"));
+                let mut file = create(&path)?;
+                // 4:4:4 avoids needing even dimensions
+                writeln!(
+                    file,
+                    "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                    settings.size.width, settings.size.height, settings.fps
+                )
+                .map_err(|err| err.to_string())?;
+                (path, Sink::Y4m(file))
+            }
+        };
+
+        self.recording = Some(Recording {
+            sink,
+            path,
+            start_time: time,
+            frame: 0,
+            total_frames: (settings.duration * settings.fps as f32).round().max(1.0) as u32,
+        });
+        self.status = None;
+        Ok(())
+    }
+
+    // Writes the frame rendered at `frame_time` from tightly packed RGBA8 `pixels`;
+    // the recording ends by itself once the duration is reached
+    pub fn record_frame(&mut self, pixels: &[u8], size: PhysicalSize<u32>) {
+        let Some(recording) = self.recording.as_mut() else {
+            return;
+        };
+        assert_eq!(
+            pixels.len(),
+            size.width as usize * size.height as usize * 4,
+            "record_frame expects {}x{} RGBA8 pixels",
+            size.width,
+            size.height
+        );
+        let result = match &mut recording.sink {
+            Sink::Png(dir) => {
+                let path = dir.join(format!("frame-{:05}.png", recording.frame));
+                screenshot::save_png(&path, pixels, size, &[])
+            }
+            Sink::Gif(file) => gif_frame(file, pixels, size, self.settings.fps).map_err(|err| err.to_string()),
+            Sink::Y4m(file) => y4m_frame(file, pixels).map_err(|err| err.to_string()),
+        };
+        recording.frame += 1;
+
+        if let Err(err) = result {
+            self.stop(Some(format!("Recording failed: {err}")));
+        } else if recording.frame >= recording.total_frames {
+            self.stop(None);
+        }
+    }
+
+    // Ends the recording when a frame couldn't be captured
+    pub fn fail(&mut self, error: &str) {
+        self.stop(Some(format!("Recording failed: {error}")));
+    }
+
+    fn stop(&mut self, error: Option<String>) {
+        let Some(recording) = self.recording.take() else {
+            return;
+        };
+        let finished = match recording.sink {
+            Sink::Png(_) => Ok(()),
+            Sink::Gif(mut file) => file.write_all(&[0x3b]).and_then(|_| file.flush()),
+            Sink::Y4m(mut file) => file.flush(),
+        };
+        let status = match (error, finished) {
+            (Some(err), _) => err,
+            (None, Err(err)) => format!("Recording failed: {err}"),
+            (None, Ok(())) => format!("Wrote {} frames to {}", recording.frame, recording.path.display()),
+        };
+        self.status = Some(status);
+    }
+
+    // `time` is the current scene time, where the recording starts from
+    pub fn ui(&mut self, ui: &mut egui::Ui, time: f32) {
+        let recording = self.is_recording();
+        ui.add_enabled_ui(!recording, |ui| {
+            let settings = &mut self.settings;
+            egui::ComboBox::from_label("Format")
+                .selected_text(match settings.format {
+                    RecordFormat::PngSequence => "PNG sequence",
+                    RecordFormat::Gif => "GIF",
+                    RecordFormat::Y4m => "Y4M",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut settings.format, RecordFormat::PngSequence, "PNG sequence");
+                    ui.selectable_value(&mut settings.format, RecordFormat::Gif, "GIF");
+                    ui.selectable_value(&mut settings.format, RecordFormat::Y4m, "Y4M");
+                });
+            ui.horizontal(|ui| {
+                ui.label("Resolution");
+                ui.add(egui::DragValue::new(&mut settings.size.width).range(16..=7680));
+                ui.label("x");
+                ui.add(egui::DragValue::new(&mut settings.size.height).range(16..=4320));
+            });
+            ui.add(egui::Slider::new(&mut settings.fps, 1..=120).text("FPS"));
+            ui.add(egui::Slider::new(&mut settings.duration, 0.1..=120.0).text("Duration (s)"));
+        });
+
+        match &self.recording {
+            Some(recording) => {
+                let progress = recording.frame as f32 / recording.total_frames as f32;
+                ui.add(
+                    egui::ProgressBar::new(progress)
+                        .text(format!("frame {} / {}", recording.frame, recording.total_frames)),
+                );
+                if ui.button("Stop").clicked() {
+                    self.stop(None);
+                }
+            }
+            None => {
+                if ui.button("Start recording").clicked() {
+                    if let Err(err) = self.start(time) {
+                        self.status = Some(format!("Recording failed: {err}"));
+                    }
+                }
+            }
+        }
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
+
+// GIF frames use a fixed 6x7x6 color cube as the global palette
+const GIF_LEVELS: [u32; 3] = [6, 7, 6];
+
+fn gif_header(file: &mut impl Write, size: PhysicalSize<u32>) -> std::io::Result<()> {
+    file.write_all(b"GIF89a")?;
+    file.write_all(&gif_dimensions(size)?)?;
+    // global color table of 2^(7 + 1) entries, 8 bits per channel
+    file.write_all(&[0xf7, 0, 0])?;
+    let mut palette = [0u8; 256 * 3];
+    for r in 0..GIF_LEVELS[0] {
+        for g in 0..GIF_LEVELS[1] {
+            for b in 0..GIF_LEVELS[2] {
+                let index = ((r * GIF_LEVELS[1] + g) * GIF_LEVELS[2] + b) as usize * 3;
+                palette[index] = (r * 255 / (GIF_LEVELS[0] - 1)) as u8;
+                palette[index + 1] = (g * 255 / (GIF_LEVELS[1] - 1)) as u8;
+                palette[index + 2] = (b * 255 / (GIF_LEVELS[2] - 1)) as u8;
+            }
+        }
+    }
+    file.write_all(&palette)?;
+    // loop forever
+    file.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00")
+}
+
+// Width and height as the little-endian u16 pairs GIF stores
+fn gif_dimensions(size: PhysicalSize<u32>) -> std::io::Result<[u8; 4]> {
+    let (Ok(width), Ok(height)) = (u16::try_from(size.width), u16::try_from(size.height)) else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("GIF frames are at most 65535x65535, not {}x{}", size.width, size.height),
+        ));
+    };
+    let [w0, w1] = width.to_le_bytes();
+    let [h0, h1] = height.to_le_bytes();
+    Ok([w0, w1, h0, h1])
+}
+
+fn gif_frame(file: &mut impl Write, pixels: &[u8], size: PhysicalSize<u32>, fps: u32) -> std::io::Result<()> {
+    // 4x4 Bayer matrix, spreads the quantization error of the coarse palette
+    const BAYER: [f32; 16] = [0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0];
+    let width = size.width as usize;
+    let indices: Vec<u8> = pixels
+        .chunks_exact(4)
+        .enumerate()
+        .map(|(i, pixel)| {
+            let threshold = BAYER[(i / width % 4) * 4 + i % width % 4] / 16.0 - 0.5;
+            let mut index = 0;
+            for c in 0..3 {
+                let steps = (GIF_LEVELS[c] - 1) as f32;
+                let level = (pixel[c] as f32 / 255.0 * steps + threshold).round().clamp(0.0, steps) as u32;
+                index = index * GIF_LEVELS[c] + level;
+            }
+            index as u8
+        })
+        .collect();
+
+    // graphic control extension with the frame delay in 1/100 s
+    let delay = (100.0 / fps as f32).round() as u16;
+    file.write_all(&[0x21, 0xf9, 0x04, 0x00])?;
+    file.write_all(&delay.to_le_bytes())?;
+    file.write_all(&[0x00, 0x00])?;
+
+    // image descriptor covering the whole canvas
+    file.write_all(&[0x2c, 0, 0, 0, 0])?;
+    file.write_all(&gif_dimensions(size)?)?;
+    file.write_all(&[0x00])?;
+
+    let data = weezl::encode::Encoder::new(weezl::BitOrder::Lsb, 8)
+        .encode(&indices)
+        .map_err(std::io::Error::other)?;
+    file.write_all(&[8])?;
+    for block in data.chunks(255) {
+        file.write_all(&[block.len() as u8])?;
+        file.write_all(block)?;
+    }
+    file.write_all(&[0])
+}
+
+// BT.601 studio range, planar Y, Cb, Cr at full resolution
+fn y4m_frame(file: &mut impl Write, pixels: &[u8]) -> std::io::Result<()> {
+    let count = pixels.len() / 4;
+    let mut planes = vec![0u8; count * 3];
+    for (i, pixel) in pixels.chunks_exact(4).enumerate() {
+        let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|v| v as f32 / 255.0);
+        planes[i] = (16.0 + 65.481 * r + 128.553 * g + 24.966 * b).round() as u8;
+        planes[count + i] = (128.0 - 37.797 * r - 74.203 * g + 112.0 * b).round() as u8;
+        planes[2 * count + i] = (128.0 + 112.0 * r - 93.786 * g - 18.214 * b).round() as u8;
+    }
+    file.write_all(b"FRAME\n")?;
+    file.write_all(&planes)
+}
@@ -0,0 +1,115 @@
+use egui_wgpu::wgpu;
+
+const MIN_SCALE: f32 = 0.25;
+const MAX_SCALE: f32 = 2.0;
+// the dynamic mode adjusts in steps of this size, so layers are not resized every frame
+const DYNAMIC_STEP: f32 = 0.05;
+// frames to wait after a change for the average to catch up
+const DYNAMIC_COOLDOWN: u32 = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Upscale {
+    Nearest,
+    Bilinear,
+}
+
+impl Upscale {
+    fn label(self) -> &'static str {
+        match self {
+            Upscale::Nearest => "Nearest",
+            Upscale::Bilinear => "Bilinear",
+        }
+    }
+
+    pub fn filter_mode(self) -> wgpu::FilterMode {
+        match self {
+            Upscale::Nearest => wgpu::FilterMode::Nearest,
+            Upscale::Bilinear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+// Resolution of the scene relative to the panel it is shown in. egui stretches
+// the result over the panel with the chosen filter.
+pub struct RenderScale {
+    pub scale: f32,
+    pub upscale: Upscale,
+    // adjust `scale` every few frames to hit `target_frame_ms`
+    pub dynamic: bool,
+    pub target_frame_ms: f32,
+    // exponential moving average of the frame time
+    average_frame_ms: f32,
+    cooldown: u32,
+}
+
+impl RenderScale {
+    pub fn new() -> Self {
+        Self {
+            scale: 1.0,
+            upscale: Upscale::Bilinear,
+            dynamic: false,
+            target_frame_ms: 1000.0 / 60.0,
+            average_frame_ms: 0.0,
+            cooldown: 0,
+        }
+    }
+
+    // Call once per frame with the wall clock frame time
+    pub fn update(&mut self, dt: f32) {
+        let frame_ms = dt * 1000.0;
+        self.average_frame_ms = if self.average_frame_ms > 0.0 {
+            self.average_frame_ms + (frame_ms - self.average_frame_ms) * 0.1
+        } else {
+            frame_ms
+        };
+        if !self.dynamic {
+            return;
+        }
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+            return;
+        }
+
+        // Frame time grows with the pixel count, i.e. with the square of the scale.
+        // Supersampling is left to the static setting.
+        let scale = if self.average_frame_ms > self.target_frame_ms * 1.1 {
+            let ratio = self.target_frame_ms / self.average_frame_ms;
+            let target = ((self.scale * ratio.sqrt()) / DYNAMIC_STEP).floor() * DYNAMIC_STEP;
+            target.min(self.scale - DYNAMIC_STEP)
+        } else if self.average_frame_ms < self.target_frame_ms {
+            self.scale + DYNAMIC_STEP
+        } else {
+            self.scale
+        };
+        let scale = scale.clamp(MIN_SCALE, 1.0);
+        if scale != self.scale {
+            self.scale = scale;
+            self.cooldown = DYNAMIC_COOLDOWN;
+        }
+    }
+
+    pub fn metadata(&self) -> String {
+        format!("{:.0}%, {}", self.scale * 100.0, self.upscale.label())
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.add_enabled(
+            !self.dynamic,
+            egui::Slider::new(&mut self.scale, MIN_SCALE..=MAX_SCALE)
+                .text("Render scale")
+                .custom_formatter(|value, _| format!("{:.0}%", value * 100.0))
+                .custom_parser(|text| text.trim_end_matches('%').trim().parse::<f64>().ok().map(|v| v / 100.0)),
+        );
+        ui.horizontal(|ui| {
+            ui.label("Upscaling");
+            for upscale in [Upscale::Nearest, Upscale::Bilinear] {
+                ui.selectable_value(&mut self.upscale, upscale, upscale.label());
+            }
+        });
+        ui.checkbox(&mut self.dynamic, "Dynamic resolution");
+        if self.dynamic {
+            ui.add(egui::Slider::new(&mut self.target_frame_ms, 4.0..=100.0).text("Target frame time (ms)"));
+            ui.label(format!("Average frame time: {:.1} ms", self.average_frame_ms));
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use crate::layer::{FrameContext, Layer, RenderTarget};
+use crate::uniform::{self, impl_uniform, UniformBuffer};
+use egui_wgpu::wgpu;
+use std::any::Any;
+
+const SHADER: &str = include_str!("screen_shader.wgsl");
+
+// `Uniforms` in screen_shader.wgsl; the layout is checked when the layer is created
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShaderUniforms {
+    resolution: [f32; 2],
+    mouse_pos: [f32; 2],
+    base_color: [f32; 4],
+}
+
+impl_uniform!(ShaderUniforms { resolution, mouse_pos, base_color });
+
+// Full-screen pass driven by `screen_shader.wgsl`
+pub struct ScreenShaderLayer {
+    pub uniforms: ShaderUniforms,
+    format: wgpu::TextureFormat,
+    cache: Option<wgpu::PipelineCache>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    render_pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: UniformBuffer<ShaderUniforms>,
+}
+
+impl ScreenShaderLayer {
+    pub fn new(device: &wgpu::Device, target_format: wgpu::TextureFormat, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+
+        // Uniform buffer
+        uniform::check_layout::<ShaderUniforms>("screen_shader.wgsl", SHADER, 0, 0);
+        let uniform_buffer = UniformBuffer::new(device, "Uniform Buffer");
+
+        // Bind group layout
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("uniform_bind_group_layout"),
+        });
+
+        // Bind group
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.binding(),
+            }],
+            label: Some("uniform_bind_group"),
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = Self::create_pipeline(device, &render_pipeline_layout, &shader, target_format, 1, cache);
+
+        Self {
+            uniforms: ShaderUniforms {
+                // time: 0.0,
+                resolution: [800.0, 600.0], // Initial window size
+                mouse_pos: [0.5, 0.5],     // Normalized coords
+                base_color: [0.1, 0.2, 0.3, 1.0],
+            },
+            format: target_format,
+            cache: cache.cloned(),
+            shader,
+            pipeline_layout: render_pipeline_layout,
+            render_pipeline,
+            bind_group,
+            uniform_buffer,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"), // 1.
+                buffers: &[], // 2.
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState { // 3.
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState { // 4.
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList, // 1.
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw, // 2.
+                cull_mode: Some(wgpu::Face::Back),
+                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                polygon_mode: wgpu::PolygonMode::Fill,
+                // Requires Features::DEPTH_CLIP_CONTROL
+                unclipped_depth: false,
+                // Requires Features::CONSERVATIVE_RASTERIZATION
+                conservative: false,
+            },
+            depth_stencil: None, // 1.
+            multisample: wgpu::MultisampleState {
+                count: sample_count, // 2.
+                mask: !0, // 3.
+                alpha_to_coverage_enabled: false, // 4.
+            },
+            multiview: None, // 5.
+            cache, // 6.
+        })
+    }
+
+    // Uploads only if something changed since the last call
+    pub fn update_uniforms(&mut self, queue: &wgpu::Queue, new_uniforms: ShaderUniforms) {
+        self.uniforms = new_uniforms;
+        self.uniform_buffer.write(queue, self.uniforms);
+    }
+}
+
+impl Layer for ScreenShaderLayer {
+    fn name(&self) -> &str {
+        "Shader"
+    }
+
+    fn restore(&mut self, old: Box<dyn Any>) {
+        let Ok(old) = old.downcast::<Self>() else {
+            return;
+        };
+        self.uniforms = old.uniforms;
+    }
+
+    fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.render_pipeline = Self::create_pipeline(device, &self.pipeline_layout, &self.shader, self.format, sample_count, self.cache.as_ref());
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        let cursor = frame.cursor.unwrap_or((0.0, 0.0));
+        let new_uniforms = ShaderUniforms {
+            resolution: [frame.size.width as f32, frame.size.height as f32],
+            mouse_pos: [cursor.0, cursor.1],
+            ..self.uniforms
+        };
+        self.update_uniforms(frame.queue, new_uniforms);
+    }
+
+    fn render(&mut self, _frame: &FrameContext, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[
+                // This is what @location(0) in the fragment shader targets
+                Some(target.color_attachment(wgpu::LoadOp::Load))
+            ],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn metadata(&self) -> Option<String> {
+        Some(format!("{:?}", self.uniforms))
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let [r, g, b, _] = self.uniforms.base_color;
+        let mut color = [r, g, b];
+        if ui.color_edit_button_rgb(&mut color).changed() {
+            self.uniforms.base_color = [color[0], color[1], color[2], 1.0];
+        }
+    }
+}
@@ -33,12 +33,8 @@ fn fs_main(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32
     // );
     
     // return vec4(color, 1.0);
-    return vec4<f32>(
-        uniforms.base_color.b,  // Blue channel
-        uniforms.base_color.g,  // Green channel
-        uniforms.base_color.r,  // Red channel
-        uniforms.base_color.a   // Alpha
-    );
+    // Linear color, as picked in the egui color picker
+    return uniforms.base_color;
 }
 
 // @fragment
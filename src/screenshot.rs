@@ -0,0 +1,154 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use winit::dpi::PhysicalSize;
+
+const SCREENSHOT_DIR: &str = "screenshots";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CaptureRequest {
+    // whole window, or just the scene viewport
+    pub include_ui: bool,
+}
+
+// Screenshot requests and results. A request made while building one frame is
+// served when the following frame is rendered, so menus have closed by then.
+pub struct Screenshots {
+    pub copy_to_clipboard: bool,
+    requested: Option<CaptureRequest>,
+    clipboard: Option<egui::ColorImage>,
+    status: Option<String>,
+}
+
+impl Screenshots {
+    pub fn new() -> Self {
+        Self {
+            copy_to_clipboard: false,
+            requested: None,
+            clipboard: None,
+            status: None,
+        }
+    }
+
+    pub fn request(&mut self, include_ui: bool) {
+        self.requested = Some(CaptureRequest { include_ui });
+    }
+
+    // Request to serve in the frame that is about to be rendered
+    pub fn take_request(&mut self) -> Option<CaptureRequest> {
+        self.requested.take()
+    }
+
+    // True while a capture or a clipboard copy waits for the next frame
+    pub fn pending(&self) -> bool {
+        self.requested.is_some() || self.clipboard.is_some()
+    }
+
+    // F12 captures the window, Shift+F12 only the scene
+    pub fn handle_hotkeys(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| i.key_pressed(egui::Key::F12)) {
+            let shift = ctx.input(|i| i.modifiers.shift);
+            self.request(!shift);
+        }
+        // Clipboard commands only reach egui-winit from inside a pass
+        if let Some(image) = self.clipboard.take() {
+            ctx.copy_image(image);
+        }
+    }
+
+    pub fn menu_ui(&mut self, ui: &mut egui::Ui) {
+        if ui.button("Screenshot (F12)").clicked() {
+            self.request(true);
+            ui.close_menu();
+        }
+        if ui.button("Screenshot without UI (Shift+F12)").clicked() {
+            self.request(false);
+            ui.close_menu();
+        }
+        ui.checkbox(&mut self.copy_to_clipboard, "Copy to clipboard");
+        if let Some(status) = &self.status {
+            ui.separator();
+            ui.label(status);
+        }
+    }
+
+    // Saves RGBA pixels read back from the GPU, with `metadata` as PNG text chunks.
+    // `note` is appended to the status, e.g. to say the capture left something out.
+    pub fn finish(&mut self, pixels: Vec<u8>, size: PhysicalSize<u32>, metadata: &[(String, String)], note: Option<&str>) {
+        let path = timestamped_path();
+        let result = std::fs::create_dir_all(SCREENSHOT_DIR)
+            .map_err(|err| err.to_string())
+            .and_then(|_| save_png(&path, &pixels, size, metadata));
+        let status = match result {
+            Ok(()) => format!("Saved {}", path.display()),
+            Err(err) => format!("Failed to save {}: {err}", path.display()),
+        };
+        let status = match note {
+            Some(note) => format!("{status} ({note})"),
+            None => status,
+        };
+        println!("{status}");
+        self.status = Some(status);
+
+        if self.copy_to_clipboard {
+            let size = [size.width as usize, size.height as usize];
+            self.clipboard = Some(egui::ColorImage::from_rgba_unmultiplied(size, &pixels));
+        }
+    }
+
+    // Reports a capture that couldn't be read back
+    pub fn fail(&mut self, error: &str) {
+        let status = format!("Screenshot failed: {error}");
+        println!("{status}");
+        self.status = Some(status);
+    }
+}
+
+pub fn save_png(path: &Path, pixels: &[u8], size: PhysicalSize<u32>, metadata: &[(String, String)]) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| err.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), size.width, size.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    for (keyword, text) in metadata {
+        encoder
+            .add_text_chunk(keyword.clone(), text.clone())
+            .map_err(|err| err.to_string())?;
+    }
+    let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+    writer.write_image_data(pixels).map_err(|err| err.to_string())
+}
+
+fn timestamped_path() -> PathBuf {
+    Path::new(SCREENSHOT_DIR).join(format!("screenshot-{}.png", timestamp()))
+}
+
+// YYYYMMDD-HHMMSS-mmm in UTC, for file names
+pub fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = now.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let time = secs % 86_400;
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}-{:03}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        now.subsec_millis()
+    )
+}
+
+// Days since 1970-01-01 to (year, month, day), from Howard Hinnant's date algorithms
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
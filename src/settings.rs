@@ -0,0 +1,105 @@
+use crate::adapter::AdapterChoice;
+use egui_wgpu::wgpu;
+use std::path::PathBuf;
+
+const SETTINGS_FILE: &str = "settings.txt";
+
+const PRESENT_MODES: [wgpu::PresentMode; 6] = [
+    wgpu::PresentMode::AutoVsync,
+    wgpu::PresentMode::AutoNoVsync,
+    wgpu::PresentMode::Fifo,
+    wgpu::PresentMode::FifoRelaxed,
+    wgpu::PresentMode::Immediate,
+    wgpu::PresentMode::Mailbox,
+];
+
+// Per-user directory for this app's configuration, None if the platform's
+// variables are missing
+pub fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.map(|base| base.join("winit-egui"))
+}
+
+// Per-user directory for data the app can rebuild, like compiled pipelines
+pub fn cache_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Caches"))
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+    };
+    base.map(|base| base.join("winit-egui"))
+}
+
+// Choices that outlive the session, stored as `key = value` lines
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub adapter: AdapterChoice,
+    pub present_mode: wgpu::PresentMode,
+    // frames the CPU may queue ahead of the GPU
+    pub frame_latency: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            adapter: AdapterChoice::Default,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            frame_latency: 2,
+        }
+    }
+}
+
+impl Settings {
+    // Falls back to the defaults for anything missing or unreadable
+    pub fn load() -> Self {
+        let text = config_dir()
+            .and_then(|dir| std::fs::read_to_string(dir.join(SETTINGS_FILE)).ok())
+            .unwrap_or_default();
+        let mut settings = Settings::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match (key.trim(), value.trim()) {
+                ("adapter", value) => settings.adapter = AdapterChoice::parse(value),
+                ("present_mode", value) => {
+                    if let Some(mode) = PRESENT_MODES.into_iter().find(|mode| format!("{mode:?}") == value) {
+                        settings.present_mode = mode;
+                    }
+                }
+                ("frame_latency", value) => {
+                    if let Ok(latency) = value.parse::<u32>() {
+                        settings.frame_latency = latency.clamp(1, 3);
+                    }
+                }
+                _ => {}
+            }
+        }
+        settings
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let dir = config_dir().ok_or("no config directory")?;
+        std::fs::create_dir_all(&dir).map_err(|err| format!("failed to create {}: {err}", dir.display()))?;
+        let path = dir.join(SETTINGS_FILE);
+        let text = format!(
+            "adapter = {}\npresent_mode = {:?}\nframe_latency = {}\n",
+            self.adapter.to_setting(),
+            self.present_mode,
+            self.frame_latency
+        );
+        std::fs::write(&path, text).map_err(|err| format!("failed to write {}: {err}", path.display()))
+    }
+}
@@ -0,0 +1,364 @@
+use crate::layer::{FrameContext, Layer, RenderTarget};
+use crate::uniform::{self, impl_uniform, UniformBuffer};
+use egui_wgpu::wgpu;
+use glam::{Affine2, Vec2};
+use std::any::Any;
+use winit::dpi::PhysicalSize;
+
+const KIND_ROUNDED_RECT: u32 = 0;
+const KIND_CIRCLE: u32 = 1;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShapeInstance {
+    axis_x: [f32; 2],
+    axis_y: [f32; 2],
+    translation: [f32; 2],
+    half_size: [f32; 2],
+    color: [f32; 4],
+    // corner radius for rects, unused for circles
+    radius: f32,
+    kind: u32,
+}
+
+impl ShapeInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x2,
+        3 => Float32x2,
+        4 => Float32x4,
+        5 => Float32,
+        6 => Uint32,
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ShapeInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShapeUniforms {
+    resolution: [f32; 2],
+    _padding: [f32; 2],
+}
+
+impl_uniform!(ShapeUniforms { resolution, _padding });
+
+// Immediate-mode 2D batcher. Shapes are queued in pixel coordinates (origin top-left,
+// mapped through the current transform) and drawn with a single instanced draw call.
+// Colors are linear RGBA with straight alpha.
+pub struct ShapeRenderer {
+    instances: Vec<ShapeInstance>,
+    transform: Affine2,
+    instance_buffer: wgpu::Buffer,
+    uniform_buffer: UniformBuffer<ShapeUniforms>,
+    bind_group: wgpu::BindGroup,
+    format: wgpu::TextureFormat,
+    cache: Option<wgpu::PipelineCache>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ShapeRenderer {
+    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat, cache: Option<&wgpu::PipelineCache>) -> Self {
+        // Shader module
+        let source = include_str!("shape_shader.wgsl");
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shape Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        uniform::check_layout::<ShapeUniforms>("shape_shader.wgsl", source, 0, 0);
+        let uniform_buffer = UniformBuffer::new(device, "Shape Uniform Buffer");
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("shape_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.binding(),
+            }],
+            label: Some("shape_bind_group"),
+        });
+
+        // Pipeline layout
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shape Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // Render pipeline
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, &shader, surface_format, 1, cache);
+
+        Self {
+            instances: Vec::new(),
+            transform: Affine2::IDENTITY,
+            instance_buffer: Self::create_instance_buffer(device, 256),
+            uniform_buffer,
+            bind_group,
+            format: surface_format,
+            cache: cache.cloned(),
+            shader,
+            pipeline_layout,
+            pipeline,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shape Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[ShapeInstance::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // mirrored transforms flip the winding
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache,
+        })
+    }
+
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.pipeline = Self::create_pipeline(device, &self.pipeline_layout, &self.shader, self.format, sample_count, self.cache.as_ref());
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shape Instance Buffer"),
+            size: (capacity * std::mem::size_of::<ShapeInstance>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // Applied to every shape queued after this call
+    pub fn set_transform(&mut self, transform: Affine2) {
+        self.transform = transform;
+    }
+
+    pub fn rect(&mut self, min: Vec2, max: Vec2, color: [f32; 4]) {
+        self.rounded_rect(min, max, 0.0, color);
+    }
+
+    pub fn rounded_rect(&mut self, min: Vec2, max: Vec2, radius: f32, color: [f32; 4]) {
+        let half_size = (max - min).abs() * 0.5;
+        let radius = radius.clamp(0.0, half_size.min_element());
+        let local = Affine2::from_translation((min + max) * 0.5);
+        self.push(local, half_size, radius, KIND_ROUNDED_RECT, color);
+    }
+
+    pub fn circle(&mut self, center: Vec2, radius: f32, color: [f32; 4]) {
+        let local = Affine2::from_translation(center);
+        self.push(local, Vec2::splat(radius), 0.0, KIND_CIRCLE, color);
+    }
+
+    // Segment with round caps
+    pub fn line(&mut self, a: Vec2, b: Vec2, thickness: f32, color: [f32; 4]) {
+        let half_thickness = thickness * 0.5;
+        let delta = b - a;
+        let dir = delta.try_normalize().unwrap_or(Vec2::X);
+        let local = Affine2::from_cols(dir, dir.perp(), (a + b) * 0.5);
+        let half_size = Vec2::new(delta.length() * 0.5 + half_thickness, half_thickness);
+        self.push(local, half_size, half_thickness, KIND_ROUNDED_RECT, color);
+    }
+
+    // The round caps double as round joins
+    pub fn polyline(&mut self, points: &[Vec2], thickness: f32, color: [f32; 4]) {
+        for pair in points.windows(2) {
+            self.line(pair[0], pair[1], thickness, color);
+        }
+    }
+
+    fn push(&mut self, local: Affine2, half_size: Vec2, radius: f32, kind: u32, color: [f32; 4]) {
+        let transform = self.transform * local;
+        self.instances.push(ShapeInstance {
+            axis_x: transform.matrix2.x_axis.to_array(),
+            axis_y: transform.matrix2.y_axis.to_array(),
+            translation: transform.translation.to_array(),
+            half_size: half_size.to_array(),
+            color,
+            radius,
+            kind,
+        });
+    }
+
+    // Uploads the queued shapes, growing the instance buffer when needed
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, size: PhysicalSize<u32>) {
+        let capacity = self.instance_buffer.size() as usize / std::mem::size_of::<ShapeInstance>();
+        if self.instances.len() > capacity {
+            self.instance_buffer = Self::create_instance_buffer(device, self.instances.len().next_power_of_two());
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.instances));
+
+        let uniforms = ShapeUniforms {
+            resolution: [size.width as f32, size.height as f32],
+            _padding: [0.0; 2],
+        };
+        self.uniform_buffer.write(queue, uniforms);
+    }
+
+    // Draws everything queued since the last call in one instanced draw, then
+    // starts a new batch
+    pub fn render(&mut self, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget) {
+        if !self.instances.is_empty() {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shape Pass"),
+                color_attachments: &[Some(target.color_attachment(wgpu::LoadOp::Load))],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+            render_pass.draw(0..4, 0..self.instances.len() as u32);
+        }
+        self.instances.clear();
+        self.transform = Affine2::IDENTITY;
+    }
+}
+
+// Animated showcase of every primitive the batcher supports
+pub struct ShapesLayer {
+    shapes: ShapeRenderer,
+    opacity: f32,
+    spin: bool,
+}
+
+impl ShapesLayer {
+    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat, cache: Option<&wgpu::PipelineCache>) -> Self {
+        Self {
+            shapes: ShapeRenderer::new(device, surface_format, cache),
+            opacity: 0.9,
+            spin: true,
+        }
+    }
+}
+
+impl Layer for ShapesLayer {
+    fn name(&self) -> &str {
+        "Shapes"
+    }
+
+    fn restore(&mut self, old: Box<dyn Any>) {
+        let Ok(old) = old.downcast::<Self>() else {
+            return;
+        };
+        self.opacity = old.opacity;
+        self.spin = old.spin;
+    }
+
+    fn animating(&self) -> bool {
+        self.spin
+    }
+
+    fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.shapes.set_sample_count(device, sample_count);
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        let size = Vec2::new(frame.size.width as f32, frame.size.height as f32);
+        let center = size * 0.5;
+        let unit = size.min_element() / 10.0;
+        let time = if self.spin { frame.time } else { 0.0 };
+        let alpha = self.opacity;
+        let shapes = &mut self.shapes;
+
+        shapes.rect(center + Vec2::new(-4.0, -3.5) * unit, center + Vec2::new(-2.0, -1.5) * unit, [0.8, 0.2, 0.1, alpha]);
+        shapes.rounded_rect(
+            center + Vec2::new(-1.0, -3.5) * unit,
+            center + Vec2::new(1.0, -1.5) * unit,
+            0.4 * unit,
+            [0.1, 0.6, 0.2, alpha],
+        );
+        shapes.circle(center + Vec2::new(3.0, -2.5) * unit, unit, [0.1, 0.3, 0.9, alpha]);
+
+        // Sine wave polyline
+        let wave: Vec<Vec2> = (0..=64)
+            .map(|i| {
+                let t = i as f32 / 64.0;
+                center + Vec2::new((t - 0.5) * 8.0, 1.0 + 0.8 * (t * 12.0 + time * 2.0).sin()) * unit
+            })
+            .collect();
+        shapes.polyline(&wave, 0.08 * unit, [1.0, 0.8, 0.1, alpha]);
+
+        // Spinning pinwheel drawn through a per-shape transform
+        shapes.set_transform(Affine2::from_angle_translation(time, center + Vec2::new(0.0, 3.0) * unit));
+        for i in 0..6 {
+            let dir = Vec2::from_angle(i as f32 * std::f32::consts::TAU / 6.0);
+            shapes.line(Vec2::ZERO, dir * unit, 0.15 * unit, [0.9, 0.9, 0.9, alpha]);
+        }
+        shapes.rect(Vec2::splat(-0.2 * unit), Vec2::splat(0.2 * unit), [0.9, 0.1, 0.6, alpha]);
+        shapes.set_transform(Affine2::IDENTITY);
+
+        if let Some((x, y)) = frame.cursor {
+            shapes.circle(Vec2::new(x, y), 0.15 * unit, [1.0, 1.0, 1.0, alpha]);
+        }
+
+        shapes.prepare(frame.device, frame.queue, frame.size);
+    }
+
+    fn render(&mut self, _frame: &FrameContext, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget) {
+        self.shapes.render(encoder, target);
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.opacity, 0.0..=1.0).text("Opacity"));
+        ui.checkbox(&mut self.spin, "Animate");
+    }
+}
@@ -0,0 +1,68 @@
+// shape_shader.wgsl
+struct Uniforms {
+    // target size in physical pixels
+    resolution: vec2<f32>,
+    _padding: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+// One instance per shape; the axes and translation map shape space to pixels
+struct InstanceInput {
+    @location(0) axis_x: vec2<f32>,
+    @location(1) axis_y: vec2<f32>,
+    @location(2) translation: vec2<f32>,
+    @location(3) half_size: vec2<f32>,
+    @location(4) color: vec4<f32>,
+    @location(5) radius: f32,
+    @location(6) kind: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) local: vec2<f32>,
+    @location(1) @interpolate(flat) half_size: vec2<f32>,
+    @location(2) @interpolate(flat) color: vec4<f32>,
+    @location(3) @interpolate(flat) radius: f32,
+    @location(4) @interpolate(flat) kind: u32,
+};
+
+const KIND_CIRCLE: u32 = 1u;
+// extra border so antialiased edges aren't clipped by the quad
+const AA_MARGIN: f32 = 1.0;
+
+// Expands a quad (triangle strip, 4 vertices) around each instance
+@vertex
+fn vs_main(@builtin(vertex_index) vert_index: u32, instance: InstanceInput) -> VertexOutput {
+    let corner = vec2(f32(vert_index & 1u), f32(vert_index >> 1u)) * 2.0 - 1.0;
+    let local = corner * (instance.half_size + AA_MARGIN);
+    let pixel = instance.translation + instance.axis_x * local.x + instance.axis_y * local.y;
+
+    var out: VertexOutput;
+    out.clip_position = vec4(pixel / uniforms.resolution * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0);
+    out.local = local;
+    out.half_size = instance.half_size;
+    out.color = instance.color;
+    out.radius = instance.radius;
+    out.kind = instance.kind;
+    return out;
+}
+
+fn rounded_rect(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half_size + radius;
+    return length(max(q, vec2(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var d: f32;
+    if in.kind == KIND_CIRCLE {
+        d = length(in.local) - in.half_size.x;
+    } else {
+        d = rounded_rect(in.local, in.half_size, in.radius);
+    }
+    // distance in screen pixels, whatever the instance scale
+    let coverage = clamp(0.5 - d / max(fwidth(d), 1e-4), 0.0, 1.0);
+    return vec4(in.color.rgb, in.color.a * coverage);
+}
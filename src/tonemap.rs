@@ -0,0 +1,192 @@
+use crate::uniform::{self, impl_uniform, UniformBuffer};
+use egui_wgpu::wgpu;
+
+// Format of the scene target the layers draw into; values are linear and unbounded
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tonemapper {
+    // cuts everything above 1, leaving displayable colors untouched
+    Clamp,
+    Reinhard,
+    Aces,
+    AgX,
+}
+
+impl Tonemapper {
+    const ALL: [Tonemapper; 4] = [Tonemapper::Clamp, Tonemapper::Reinhard, Tonemapper::Aces, Tonemapper::AgX];
+
+    fn label(self) -> &'static str {
+        match self {
+            Tonemapper::Clamp => "Clamp",
+            Tonemapper::Reinhard => "Reinhard",
+            Tonemapper::Aces => "ACES",
+            Tonemapper::AgX => "AgX",
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TonemapUniforms {
+    exposure: f32,
+    // index into `Tonemapper::ALL`, matching the CURVE_ constants in the shader
+    curve: u32,
+    _padding: [f32; 2],
+}
+
+impl_uniform!(TonemapUniforms { exposure, curve, _padding });
+
+// Maps the HDR scene target to the display format with an exposure adjustment
+pub struct TonemapPass {
+    // in stops; the scene is scaled by 2^exposure
+    pub exposure: f32,
+    pub curve: Tonemapper,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: UniformBuffer<TonemapUniforms>,
+}
+
+impl TonemapPass {
+    pub fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let source = include_str!("tonemap.wgsl");
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Tonemap Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        uniform::check_layout::<TonemapUniforms>("tonemap.wgsl", source, 0, 0);
+        let uniform_buffer = UniformBuffer::new(device, "Tonemap Uniform Buffer");
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("tonemap_bind_group_layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Tonemap Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Tonemap Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: output_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache,
+        });
+
+        Self {
+            exposure: 0.0,
+            curve: Tonemapper::Clamp,
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+        }
+    }
+
+    // `hdr` and `output` must have the same size
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        hdr: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let uniforms = TonemapUniforms {
+            exposure: self.exposure.exp2(),
+            curve: Tonemapper::ALL.iter().position(|&curve| curve == self.curve).unwrap() as u32,
+            _padding: [0.0; 2],
+        };
+        self.uniform_buffer.write(queue, uniforms);
+
+        // The HDR view is recreated on resize, so bind it fresh every frame
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform_buffer.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(hdr),
+                },
+            ],
+            label: Some("tonemap_bind_group"),
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Tonemap Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    pub fn metadata(&self) -> String {
+        format!("{}, exposure {:+.2} EV", self.curve.label(), self.exposure)
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("Tonemapping")
+            .selected_text(self.curve.label())
+            .show_ui(ui, |ui| {
+                for curve in Tonemapper::ALL {
+                    ui.selectable_value(&mut self.curve, curve, curve.label());
+                }
+            });
+        ui.add(egui::Slider::new(&mut self.exposure, -8.0..=8.0).text("Exposure (EV)"));
+    }
+}
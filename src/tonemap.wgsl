@@ -0,0 +1,84 @@
+struct Uniforms {
+    exposure: f32,
+    curve: u32,
+    _padding: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+@group(0) @binding(1)
+var hdr: texture_2d<f32>;
+
+// Renders a full-screen triangle without vertex data
+@vertex
+fn vs_main(@builtin(vertex_index) vert_index: u32) -> @builtin(position) vec4<f32> {
+    let pos = array(
+        vec2(-1.0, -1.0),
+        vec2(3.0, -1.0),
+        vec2(-1.0, 3.0),
+    );
+    return vec4(pos[vert_index], 0.0, 1.0);
+}
+
+const CURVE_REINHARD: u32 = 1u;
+const CURVE_ACES: u32 = 2u;
+const CURVE_AGX: u32 = 3u;
+
+fn reinhard(x: vec3<f32>) -> vec3<f32> {
+    return x / (1.0 + x);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), vec3(0.0), vec3(1.0));
+}
+
+// Polynomial fit of the AgX default contrast curve, from Benjamin Wrensch's minimal AgX
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+fn agx(x: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+    );
+    let outset = mat3x3<f32>(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+    var v = inset * max(x, vec3(1e-10));
+    v = (clamp(log2(v), vec3(min_ev), vec3(max_ev)) - min_ev) / (max_ev - min_ev);
+    v = outset * agx_contrast(v);
+    // the curve produces display-encoded values; return to linear for the target
+    return pow(max(v, vec3(0.0)), vec3(2.2));
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let color = textureLoad(hdr, vec2<i32>(frag_position.xy), 0);
+    let exposed = max(color.rgb * uniforms.exposure, vec3(0.0));
+    var mapped: vec3<f32>;
+    switch uniforms.curve {
+        case CURVE_REINHARD: {
+            mapped = reinhard(exposed);
+        }
+        case CURVE_ACES: {
+            mapped = aces(exposed);
+        }
+        case CURVE_AGX: {
+            mapped = agx(exposed);
+        }
+        default: {
+            mapped = min(exposed, vec3(1.0));
+        }
+    }
+    return vec4(mapped, clamp(color.a, 0.0, 1.0));
+}
@@ -0,0 +1,196 @@
+use egui_wgpu::wgpu;
+use egui_wgpu::wgpu::naga;
+
+// Where a field of a uniform struct sits in memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub offset: u32,
+    pub size: u32,
+}
+
+// A `#[repr(C)]` struct mirrored by a WGSL struct in a uniform binding. Fields
+// starting with `_` are padding and may be missing on the WGSL side.
+pub trait Uniform: bytemuck::Pod {
+    fn fields() -> Vec<Field>;
+}
+
+// Implements `Uniform` from the struct's field names:
+// `impl_uniform!(Uniforms { resolution, color, _padding });`
+macro_rules! impl_uniform {
+    ($ty:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::uniform::Uniform for $ty {
+            fn fields() -> Vec<$crate::uniform::Field> {
+                fn size_of_field<T, F>(_: fn(&T) -> &F) -> u32 {
+                    std::mem::size_of::<F>() as u32
+                }
+                vec![$($crate::uniform::Field {
+                    name: stringify!($field),
+                    offset: std::mem::offset_of!($ty, $field) as u32,
+                    size: size_of_field(|value: &$ty| &value.$field),
+                }),*]
+            }
+        }
+    };
+}
+pub(crate) use impl_uniform;
+
+// Compares `T` with the struct naga reports for `@group(group) @binding(binding)`
+// in `source` and panics listing every difference, since a shader reading a
+// mismatched layout silently draws garbage. `label` names the shader in the message.
+pub fn check_layout<T: Uniform>(label: &str, source: &str, group: u32, binding: u32) {
+    let module = naga::front::wgsl::parse_str(source)
+        .unwrap_or_else(|err| panic!("Failed to parse {label}: {}", err.emit_to_string(source)));
+    let rust_name = std::any::type_name::<T>();
+    let location = format!("@group({group}) @binding({binding}) in {label}");
+    let variable = module
+        .global_variables
+        .iter()
+        .map(|(_, variable)| variable)
+        .find(|variable| variable.binding == Some(naga::ResourceBinding { group, binding }))
+        .unwrap_or_else(|| panic!("{rust_name}: nothing is bound at {location}"));
+    let naga::TypeInner::Struct { members, span } = &module.types[variable.ty].inner else {
+        panic!("{rust_name}: the binding at {location} is not a struct");
+    };
+
+    let fields = T::fields();
+    let mut errors = Vec::new();
+    for member in members {
+        let name = member.name.as_deref().unwrap_or("<unnamed>");
+        let size = module.types[member.ty].inner.size(module.to_ctx());
+        match fields.iter().find(|field| field.name == name) {
+            None => errors.push(format!("`{name}` (offset {}, {size} bytes) has no Rust field", member.offset)),
+            Some(field) if field.offset != member.offset || field.size != size => errors.push(format!(
+                "`{name}` is at offset {} with {} bytes in Rust but at offset {} with {size} bytes in WGSL",
+                field.offset, field.size, member.offset
+            )),
+            Some(_) => {}
+        }
+    }
+    for field in fields.iter().filter(|field| !field.name.starts_with('_')) {
+        if !members.iter().any(|member| member.name.as_deref() == Some(field.name)) {
+            errors.push(format!("`{}` is not in the WGSL struct", field.name));
+        }
+    }
+    let rust_size = std::mem::size_of::<T>() as u32;
+    if rust_size != *span {
+        errors.push(format!("the Rust struct is {rust_size} bytes, the WGSL struct {span}"));
+    }
+    if !errors.is_empty() {
+        panic!("{rust_name} does not match the uniform at {location}:\n  {}", errors.join("\n  "));
+    }
+}
+
+// Buffer holding one `T`, written only when the value changes
+pub struct UniformBuffer<T: Uniform> {
+    buffer: wgpu::Buffer,
+    // what the buffer holds, None before the first write
+    uploaded: Option<T>,
+}
+
+impl<T: Uniform> UniformBuffer<T> {
+    pub fn new(device: &wgpu::Device, label: &str) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: std::mem::size_of::<T>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self { buffer, uploaded: None }
+    }
+
+    pub fn binding(&self) -> wgpu::BindingResource<'_> {
+        self.buffer.as_entire_binding()
+    }
+
+    // Skips the upload when `value` is byte for byte what the buffer already holds
+    pub fn write(&mut self, queue: &wgpu::Queue, value: T) {
+        if self
+            .uploaded
+            .is_some_and(|uploaded| bytemuck::bytes_of(&uploaded) == bytemuck::bytes_of(&value))
+        {
+            return;
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&value));
+        self.uploaded = Some(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+    struct Params {
+        color: [f32; 4],
+        scale: [f32; 2],
+        time: f32,
+        _padding: f32,
+    }
+
+    impl_uniform!(Params { color, scale, time, _padding });
+
+    // Params without the trailing padding, 4 bytes short of the WGSL struct
+    #[repr(C)]
+    #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+    struct Unpadded {
+        color: [f32; 4],
+        scale: [f32; 2],
+        time: f32,
+    }
+
+    impl_uniform!(Unpadded { color, scale, time });
+
+    fn shader(members: &str) -> String {
+        format!("struct Params {{ {members} }}; @group(1) @binding(2) var<uniform> params: Params;")
+    }
+
+    #[test]
+    fn accepts_matching_layout() {
+        // `_padding` may be left out on the WGSL side
+        check_layout::<Params>("test", &shader("color: vec4<f32>, scale: vec2<f32>, time: f32"), 1, 2);
+        check_layout::<Params>(
+            "test",
+            &shader("color: vec4<f32>, scale: vec2<f32>, time: f32, _padding: f32"),
+            1,
+            2,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`time` is at offset 24 with 4 bytes in Rust but at offset 16 with 4 bytes in WGSL")]
+    fn rejects_wrong_offset() {
+        check_layout::<Params>("test", &shader("color: vec4<f32>, time: f32, scale: vec2<f32>"), 1, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "`color` is at offset 0 with 16 bytes in Rust but at offset 0 with 12 bytes in WGSL")]
+    fn rejects_wrong_size() {
+        check_layout::<Params>("test", &shader("color: vec3<f32>, scale: vec2<f32>, time: f32"), 1, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "`time` is not in the WGSL struct")]
+    fn rejects_missing_field() {
+        check_layout::<Params>("test", &shader("color: vec4<f32>, scale: vec2<f32>"), 1, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "`extra` (offset 28, 4 bytes) has no Rust field")]
+    fn rejects_extra_field() {
+        check_layout::<Params>("test", &shader("color: vec4<f32>, scale: vec2<f32>, time: f32, extra: f32"), 1, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "the Rust struct is 28 bytes, the WGSL struct 32")]
+    fn rejects_total_size_mismatch() {
+        check_layout::<Unpadded>("test", &shader("color: vec4<f32>, scale: vec2<f32>, time: f32"), 1, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "nothing is bound at @group(0) @binding(0) in test")]
+    fn rejects_wrong_binding() {
+        check_layout::<Params>("test", &shader("color: vec4<f32>, scale: vec2<f32>, time: f32"), 0, 0);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::layer::{LayerInput, RenderTarget, DEPTH_FORMAT};
+use crate::tonemap::HDR_FORMAT;
+use egui_wgpu::{wgpu, Renderer};
+use winit::dpi::PhysicalSize;
+use winit::event::MouseButton;
+
+// Format of the tonemapped scene image. Always sRGB so egui reads back the same
+// linear colors whatever format the surface ended up with.
+pub const DISPLAY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// egui-winit turns one wheel notch into this many points
+const POINTS_PER_SCROLL_LINE: f32 = 50.0;
+
+// Offscreen targets the layer stack renders into. Layers draw into an HDR texture
+// that is tonemapped into `texture`, which is shown as an image inside egui.
+// Their size follows the panel they are placed in rather than the window, times
+// the render scale.
+pub struct SceneViewport {
+    size: PhysicalSize<u32>,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    texture_id: egui::TextureId,
+    // how egui samples `texture` when stretching it over the panel
+    filter: wgpu::FilterMode,
+    hdr_view: wgpu::TextureView,
+    sample_count: u32,
+    // multisampled color target resolved into `hdr_view`, when sample_count > 1
+    msaa_view: Option<wgpu::TextureView>,
+    // only kept while some layer draws depth-tested geometry
+    depth_view: Option<wgpu::TextureView>,
+    // render at this size instead of following the panel; the image is letterboxed
+    fixed_size: Option<PhysicalSize<u32>>,
+    // panel pixels are multiplied by this to get the render size
+    render_scale: f32,
+    // physical pixels relative to the viewport's top-left corner
+    cursor: Option<(f32, f32)>,
+    rect: egui::Rect,
+}
+
+impl SceneViewport {
+    pub fn new(
+        device: &wgpu::Device,
+        renderer: &mut Renderer,
+        size: PhysicalSize<u32>,
+    ) -> Self {
+        let size = PhysicalSize::new(size.width.max(1), size.height.max(1));
+        let (texture, view) = Self::create_texture(device, size);
+        let texture_id = renderer.register_native_texture(device, &view, wgpu::FilterMode::Linear);
+        let hdr_view = Self::create_hdr_view(device, size);
+        Self {
+            size,
+            texture,
+            view,
+            texture_id,
+            filter: wgpu::FilterMode::Linear,
+            hdr_view,
+            sample_count: 1,
+            msaa_view: None,
+            depth_view: None,
+            fixed_size: None,
+            render_scale: 1.0,
+            cursor: None,
+            rect: egui::Rect::NOTHING,
+        }
+    }
+
+    fn create_texture(device: &wgpu::Device, size: PhysicalSize<u32>) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Scene Viewport Texture"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DISPLAY_FORMAT,
+            // COPY_SRC for screenshots without the UI
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    fn create_hdr_view(device: &wgpu::Device, size: PhysicalSize<u32>) -> wgpu::TextureView {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Scene Viewport HDR Texture"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            // read by the tonemapping pass
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    // Render-only texture matching the scene size, e.g. for MSAA or depth
+    fn create_attachment(
+        device: &wgpu::Device,
+        label: &str,
+        format: wgpu::TextureFormat,
+        size: PhysicalSize<u32>,
+        sample_count: u32,
+    ) -> wgpu::TextureView {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    // Recreates the MSAA and depth attachments for the current size and sample count
+    fn rebuild_attachments(&mut self, device: &wgpu::Device) {
+        self.msaa_view = (self.sample_count > 1).then(|| {
+            Self::create_attachment(device, "Scene Viewport MSAA Texture", HDR_FORMAT, self.size, self.sample_count)
+        });
+        if self.depth_view.is_some() {
+            self.depth_view = Some(Self::create_attachment(
+                device,
+                "Scene Viewport Depth Texture",
+                DEPTH_FORMAT,
+                self.size,
+                self.sample_count,
+            ));
+        }
+    }
+
+    // Registers the texture with a freshly created egui renderer
+    pub fn register(&mut self, device: &wgpu::Device, renderer: &mut Renderer) {
+        self.texture_id = renderer.register_native_texture(device, &self.view, self.filter);
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.size
+    }
+
+    // The tonemapped image shown in egui
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn hdr_view(&self) -> &wgpu::TextureView {
+        &self.hdr_view
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    // Where the layer stack draws; resolves into `hdr_view` when multisampled
+    pub fn target(&self) -> RenderTarget<'_> {
+        match &self.msaa_view {
+            Some(msaa_view) => RenderTarget {
+                view: msaa_view,
+                resolve_target: Some(&self.hdr_view),
+                depth: self.depth_view.as_ref(),
+            },
+            None => RenderTarget {
+                depth: self.depth_view.as_ref(),
+                ..RenderTarget::single(&self.hdr_view)
+            },
+        }
+    }
+
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.rebuild_attachments(device);
+    }
+
+    pub fn set_depth(&mut self, device: &wgpu::Device, enabled: bool) {
+        if enabled == self.depth_view.is_some() {
+            return;
+        }
+        self.depth_view = enabled.then(|| {
+            Self::create_attachment(device, "Scene Viewport Depth Texture", DEPTH_FORMAT, self.size, self.sample_count)
+        });
+    }
+
+    pub fn cursor(&self) -> Option<(f32, f32)> {
+        self.cursor
+    }
+
+    pub fn set_fixed_size(&mut self, size: Option<PhysicalSize<u32>>) {
+        self.fixed_size = size;
+    }
+
+    // Takes effect on the next `sync_size`; ignored while the size is fixed
+    pub fn set_render_scale(&mut self, scale: f32) {
+        self.render_scale = scale;
+    }
+
+    pub fn set_filter(&mut self, device: &wgpu::Device, renderer: &mut Renderer, filter: wgpu::FilterMode) {
+        if filter != self.filter {
+            self.filter = filter;
+            renderer.update_egui_texture_from_wgpu_texture(device, &self.view, filter, self.texture_id);
+        }
+    }
+
+    // Screen rect of the scene image in points, for painting overlays on top of it
+    pub fn rect(&self) -> egui::Rect {
+        self.rect
+    }
+
+    // Fills the remaining space of `ui` with the scene image and returns the pointer
+    // input aimed at it. Call `sync_size` afterwards to follow panel resizes.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> Vec<LayerInput> {
+        let panel = ui.available_rect_before_wrap();
+        let rect = match self.fixed_size {
+            Some(size) => {
+                let aspect = size.width as f32 / size.height as f32;
+                let fitted = egui::vec2(panel.width().min(panel.height() * aspect), panel.height().min(panel.width() / aspect));
+                egui::Rect::from_center_size(panel.center(), fitted)
+            }
+            None => panel,
+        };
+        let response = ui.allocate_rect(rect, egui::Sense::click_and_drag());
+        ui.painter().image(
+            self.texture_id,
+            rect,
+            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+            egui::Color32::WHITE,
+        );
+        self.rect = rect;
+
+        // texture pixels per point along each axis
+        let scale = egui::vec2(self.size.width as f32, self.size.height as f32) / rect.size();
+        let to_pixels = |pos: egui::Pos2| {
+            let pixel = (pos - rect.min) * scale;
+            (pixel.x, pixel.y)
+        };
+        self.cursor = response.hover_pos().map(to_pixels);
+
+        let mut events = Vec::new();
+        if response.dragged_by(egui::PointerButton::Primary) {
+            let delta = response.drag_delta() * scale;
+            events.push(LayerInput::Drag { delta: (delta.x, delta.y) });
+        }
+        if let Some(position) = self.cursor {
+            let scroll = ui.input(|i| i.raw_scroll_delta.y) / POINTS_PER_SCROLL_LINE;
+            if scroll != 0.0 {
+                events.push(LayerInput::Scroll { delta: scroll, position });
+            }
+        }
+        if response.clicked() {
+            events.push(LayerInput::Click { button: MouseButton::Left });
+        }
+        if response.secondary_clicked() {
+            events.push(LayerInput::Click { button: MouseButton::Right });
+        }
+        events
+    }
+
+    // Recreates the target when the panel changed size; returns true if it did
+    pub fn sync_size(&mut self, device: &wgpu::Device, renderer: &mut Renderer, pixels_per_point: f32) -> bool {
+        let max = device.limits().max_texture_dimension_2d;
+        let pixels = self.rect.size() * pixels_per_point * self.render_scale;
+        let size = self.fixed_size.unwrap_or(PhysicalSize::new(
+            (pixels.x.round() as u32).clamp(1, max),
+            (pixels.y.round() as u32).clamp(1, max),
+        ));
+        if !self.rect.is_positive() || size == self.size {
+            return false;
+        }
+        self.size = size;
+        (self.texture, self.view) = Self::create_texture(device, size);
+        self.hdr_view = Self::create_hdr_view(device, size);
+        self.rebuild_attachments(device);
+        renderer.update_egui_texture_from_wgpu_texture(device, &self.view, self.filter, self.texture_id);
+        true
+    }
+}